@@ -0,0 +1,59 @@
+// Throughput of the text parser over a mix of every message form and plain text, run with
+//   cargo run --release --example ingest_bench [lines]
+extern crate scope;
+
+use scope::delimited::CsvSettings;
+use scope::ingest::{Parsed, Parser};
+use scope::json::JsonSettings;
+
+use std::env;
+use std::time::{Duration, Instant};
+
+fn input(lines: usize) -> String {
+    let mut text = String::with_capacity(lines * 32);
+    for i in 0..lines {
+        let v = i as f64 * 0.001;
+        let line = match i % 8 {
+            0..=2 => format!("~.sig{}@{}\n", i % 16, v),
+            3 => format!("~.pos@{},{},{}\n", v, -v, v * 2.),
+            4 => format!("~.imu@ax={},ay={},az={},t=+1ms\n", v, v, v),
+            5 => format!("~#m#2,2@({},{})({},{})\n", v, v, v, v),
+            6 => format!("~.clk@{},t={}us\n", v, i),
+            _ => format!("log line {} that is passed through\n", i),
+        };
+        text.push_str(&line);
+    }
+    text
+}
+
+fn main() {
+    let lines = env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1_000_000);
+    let text = input(lines);
+    let mut parser = Parser::new(CsvSettings::default(), JsonSettings::default());
+    let received = Duration::from_secs(1);
+
+    let start = Instant::now();
+    let (mut points, mut passed) = (0usize, 0usize);
+    for (n, line) in text.split_inclusive('\n').enumerate() {
+        match parser.parse(line, &received, n) {
+            Ok(Parsed::Point(_)) => points += 1,
+            Ok(Parsed::Points(p)) => points += p.len(),
+            Ok(Parsed::Passthrough) => passed += 1,
+            Err(e) => println!("{}", e),
+        }
+    }
+    let secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} lines ({} MB) in {:.3}s: {:.2}M lines/s, {:.2}M points/s, {} passed through",
+        lines,
+        text.len() / 1_000_000,
+        secs,
+        lines as f64 / secs / 1e6,
+        points as f64 / secs / 1e6,
+        passed
+    );
+}
@@ -0,0 +1,335 @@
+// Binary framing for producers that emit samples faster than text can be parsed. All integers and floats are little
+// endian, a frame is laid out as
+//
+//   sync   2 bytes   0xA5 0x5A
+//   kind   u8        NAME_FRAME or SAMPLE_FRAME
+//   len    u16       Length of the body
+//   body   len bytes
+//   check  u16       CRC-16/CCITT (polynomial 0x1021, initial 0xFFFF) of kind, len and body
+//
+// A name frame body is a u16 channel id followed by the utf8 name of the signal. A sample body is a u16 channel id,
+// a flags byte, a u64 timestamp in us if FLAG_TIME is set and then dimension f32 values, or f64 values if FLAG_F64 is
+// set. A dimension of 0 is a breakpoint. Channels that were never named are called bin.<id>.
+//
+// Bodies are at most MAX_BODY bytes. Corrupt frames are dropped a byte at a time until the next sync pattern that
+// starts a frame with a valid checksum. What is dropped goes to stderr, stdout carries passed through text.
+use ingest::{MsgPoint, Name, ParseError, PointType};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const SYNC: [u8; 2] = [0xA5, 0x5A];
+pub const NAME_FRAME: u8 = 1;
+pub const SAMPLE_FRAME: u8 = 2;
+
+pub const FLAG_DIMENSION: u8 = 0b0011; // Number of values, 0 to 3
+pub const FLAG_F64: u8 = 0b0100;
+pub const FLAG_TIME: u8 = 0b1000;
+
+const HEADER: usize = 5; // sync, kind and len
+const CHECK: usize = 2;
+const MAX_BODY: usize = 512; // Longer frames are taken to be corruption rather than waited for
+
+pub struct Decoder {
+    names: HashMap<u16, Name>,
+    buf: Vec<u8>,
+    start: usize, // Bytes of buf before this have been consumed
+    pub frames: usize,
+    pub skipped: usize, // Bytes thrown away while looking for the next frame
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder {
+            names: HashMap::new(),
+            buf: Vec::new(),
+            start: 0,
+            frames: 0,
+            skipped: 0,
+        }
+    }
+
+    // Appends bytes to what is left of earlier reads and decodes every complete frame, samples are stamped with
+    // timestamp as their receive time and numbered by frame
+    pub fn feed(&mut self, bytes: &[u8], timestamp: f64) -> Vec<Result<MsgPoint, ParseError>> {
+        self.buf.extend_from_slice(bytes);
+        let mut out = Vec::new();
+        let mut skipped = 0;
+        loop {
+            let rest = &self.buf[self.start..];
+            if rest.len() < HEADER {
+                break;
+            }
+            if rest[..2] != SYNC {
+                self.start += 1;
+                skipped += 1;
+                continue;
+            }
+            let len = u16::from_le_bytes([rest[3], rest[4]]) as usize;
+            if len > MAX_BODY {
+                self.start += 1;
+                skipped += 1;
+                continue;
+            }
+            let total = HEADER + len + CHECK;
+            if rest.len() < total {
+                break; // Wait for the rest of the frame
+            }
+            let check = u16::from_le_bytes([rest[total - 2], rest[total - 1]]);
+            if crc16(&rest[2..HEADER + len]) != check {
+                // Not a frame after all, the sync pattern was in the middle of one or the frame is corrupt
+                self.start += 1;
+                skipped += 1;
+                continue;
+            }
+            let kind = rest[2];
+            let body = rest[HEADER..HEADER + len].to_vec();
+            self.start += total;
+            match kind {
+                NAME_FRAME if len >= 2 => {
+                    let id = u16::from_le_bytes([body[0], body[1]]);
+                    let name = String::from_utf8_lossy(&body[2..]);
+                    self.names.insert(id, Arc::from(&*name));
+                }
+                SAMPLE_FRAME if len >= 3 => out.push(self.sample(&body, timestamp)),
+                _ => eprintln!("Ignoring binary frame of kind {} with {} bytes", kind, len),
+            }
+            self.frames += 1; // After the sample so frames are numbered from 0 like lines
+        }
+        if skipped > 0 {
+            eprintln!(
+                "Skipped {} bytes of binary input looking for a frame",
+                skipped
+            );
+            self.skipped += skipped;
+        }
+        self.buf.drain(..self.start);
+        self.start = 0;
+        out
+    }
+
+    fn sample(&mut self, body: &[u8], timestamp: f64) -> Result<MsgPoint, ParseError> {
+        let id = u16::from_le_bytes([body[0], body[1]]);
+        let name = self
+            .names
+            .entry(id)
+            .or_insert_with(|| Arc::from(format!("bin.{}", id)))
+            .clone();
+        let flags = body[2];
+        let dims = (flags & FLAG_DIMENSION) as usize;
+        let width = if flags & FLAG_F64 != 0 { 8 } else { 4 };
+        let time = if flags & FLAG_TIME != 0 { 8 } else { 0 };
+        let expected = 3 + time + dims * width;
+        if body.len() != expected {
+            return Err(ParseError::InvalidFrame {
+                name: String::from(&*name),
+                len: body.len(),
+                expected,
+            });
+        }
+
+        let mut at = 3;
+        let device_time = if time > 0 {
+            at += 8;
+            u64::from_le_bytes(array8(&body[3..11])) as f64
+        } else {
+            f64::NAN
+        };
+        let mut v = [f64::NAN; 3];
+        for d in v.iter_mut().take(dims) {
+            *d = if width == 8 {
+                f64::from_le_bytes(array8(&body[at..at + 8]))
+            } else {
+                f32::from_le_bytes([body[at], body[at + 1], body[at + 2], body[at + 3]]) as f64
+            };
+            at += width;
+        }
+        let ty = match dims {
+            0 => PointType::BreakPoint,
+            1 => PointType::D1,
+            2 => PointType::D2,
+            _ => PointType::D3,
+        };
+        Ok(MsgPoint {
+            name,
+            line_number: self.frames,
+            timestamp,
+            device_time,
+            ty,
+            x: v[0],
+            y: v[1],
+            z: v[2],
+            values: Vec::new(),
+            shape: (0, 0),
+        })
+    }
+}
+
+// Builds a frame around body, for producers written in rust and for testing the decoder
+pub fn encode(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER + body.len() + CHECK);
+    frame.extend_from_slice(&SYNC);
+    frame.push(kind);
+    frame.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    frame.extend_from_slice(body);
+    let check = crc16(&frame[2..]);
+    frame.extend_from_slice(&check.to_le_bytes());
+    frame
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for d in data {
+        crc ^= (*d as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn array8(s: &[u8]) -> [u8; 8] {
+    let mut a = [0u8; 8];
+    a.copy_from_slice(s);
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(id: u16, name: &str) -> Vec<u8> {
+        let mut body = id.to_le_bytes().to_vec();
+        body.extend_from_slice(name.as_bytes());
+        encode(NAME_FRAME, &body)
+    }
+
+    fn sample(id: u16, values: &[f32]) -> Vec<u8> {
+        let mut body = id.to_le_bytes().to_vec();
+        body.push(values.len() as u8);
+        for v in values {
+            body.extend_from_slice(&v.to_le_bytes());
+        }
+        encode(SAMPLE_FRAME, &body)
+    }
+
+    fn points(out: Vec<Result<MsgPoint, ParseError>>) -> Vec<(String, f64)> {
+        out.into_iter()
+            .map(|r| {
+                let p = r.unwrap();
+                (String::from(&*p.name), p.x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn checksum_is_crc16_ccitt() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn samples_are_decoded() {
+        let mut d = Decoder::new();
+        let mut bytes = name(3, "volts");
+        bytes.extend(sample(3, &[1.5]));
+        bytes.extend(sample(4, &[2., 3.]));
+        let mut body = 5u16.to_le_bytes().to_vec();
+        body.push(1 | FLAG_F64 | FLAG_TIME);
+        body.extend_from_slice(&7u64.to_le_bytes());
+        body.extend_from_slice(&0.25f64.to_le_bytes());
+        bytes.extend(encode(SAMPLE_FRAME, &body));
+        bytes.extend(sample(3, &[]));
+        let out = d.feed(&bytes, 100.);
+        let p: Vec<MsgPoint> = out.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(p.len(), 4);
+        assert_eq!(
+            (&*p[0].name, p[0].x, p[0].ty),
+            ("volts", 1.5, PointType::D1)
+        );
+        assert_eq!((&*p[1].name, p[1].x, p[1].y), ("bin.4", 2., 3.));
+        assert_eq!((p[2].x, p[2].device_time, p[2].timestamp), (0.25, 7., 100.));
+        assert_eq!(p[3].ty, PointType::BreakPoint);
+        assert_eq!(p[1].line_number, 2);
+        assert_eq!(d.frames, 5);
+    }
+
+    #[test]
+    fn garbage_is_skipped_up_to_the_next_frame() {
+        let mut d = Decoder::new();
+        let mut bytes = vec![0, 1, 0xA5, 2, 0xA5, 0x5A, 9];
+        bytes.extend(sample(1, &[4.]));
+        assert_eq!(
+            points(d.feed(&bytes, 0.)),
+            vec![(String::from("bin.1"), 4.)]
+        );
+        assert_eq!(d.skipped, 7);
+    }
+
+    #[test]
+    fn frames_with_a_bad_checksum_are_dropped() {
+        let mut d = Decoder::new();
+        let mut bad = sample(1, &[4.]);
+        let n = bad.len();
+        bad[n - 3] ^= 0x10;
+        bad.extend(sample(1, &[5.]));
+        assert_eq!(points(d.feed(&bad, 0.)), vec![(String::from("bin.1"), 5.)]);
+        assert_eq!(d.frames, 1);
+        assert!(d.skipped >= n);
+    }
+
+    #[test]
+    fn frames_split_across_reads_are_joined() {
+        let mut d = Decoder::new();
+        let frame = sample(2, &[1., 2., 3.]);
+        let (a, b) = frame.split_at(6);
+        assert!(d.feed(a, 0.).is_empty());
+        assert_eq!(d.skipped, 0);
+        let p = d.feed(b, 1.).pop().unwrap().unwrap();
+        assert_eq!((p.x, p.y, p.z, p.timestamp), (1., 2., 3., 1.));
+    }
+
+    #[test]
+    fn truncated_frames_give_way_to_the_next() {
+        let mut d = Decoder::new();
+        let mut bytes = sample(1, &[1., 2.]);
+        bytes.truncate(9);
+        bytes.extend(sample(1, &[3.]));
+        bytes.extend(sample(1, &[4.]));
+        let got = points(d.feed(&bytes, 0.));
+        assert_eq!(got.last(), Some(&(String::from("bin.1"), 4.)));
+        assert!(got.contains(&(String::from("bin.1"), 3.)));
+        assert!(d.skipped >= 9);
+    }
+
+    #[test]
+    fn overlong_lengths_are_not_waited_for() {
+        let mut d = Decoder::new();
+        let mut bytes = vec![0xA5, 0x5A, SAMPLE_FRAME, 0xFF, 0xFF];
+        bytes.extend(sample(1, &[1.]));
+        assert_eq!(points(d.feed(&bytes, 0.)).len(), 1);
+    }
+
+    #[test]
+    fn samples_of_the_wrong_length_are_errors() {
+        let mut d = Decoder::new();
+        let mut body = 1u16.to_le_bytes().to_vec();
+        body.extend_from_slice(&[2, 0, 0, 0, 0]);
+        match d.feed(&encode(SAMPLE_FRAME, &body), 0.).pop() {
+            Some(Err(ParseError::InvalidFrame { len, expected, .. })) => {
+                assert_eq!((len, expected), (7, 11))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -1,6 +1,9 @@
 // get suggestions given current buffer state, and parse buffer and set state
 use drawstyles::*;
-use signal::{AxisBind, SignalManager};
+use export;
+use scope::session::{Control, Speed};
+use scope::spill;
+use signal::{Acquire, AxisBind, Clock, History, SignalManager, Single, Trigger};
 
 pub struct LineState {
     pub valid: bool,
@@ -21,6 +24,39 @@ pub fn parse(line: &str, run: bool, manager: &mut SignalManager) -> LineState {
             "s" | "select" => select(line, run, &mut valid, &mut possible_completions, manager),
             "b" | "bb" | "bind" => bind(line, run, &mut valid, &mut possible_completions, manager),
             "f" | "free" => free(line, run, &mut valid, &mut possible_completions, manager),
+            "clk" | "clock" => clock(line, run, &mut valid, &mut possible_completions, manager),
+            "w" | "window" => window(line, run, &mut valid, &mut possible_completions, manager),
+            "hs" | "history" => history(line, run, &mut valid, &mut possible_completions, manager),
+            "mem" | "memory" => memory(line, run, &mut valid, &mut possible_completions, manager),
+            "hl" | "health" => health(line, run, &mut valid, &mut possible_completions, manager),
+            "qu" | "queue" => {
+                if run {
+                    queue(manager)
+                }
+            }
+            "cont" | "continue" => {
+                if run {
+                    manager.resume()
+                }
+            }
+            "bp" | "breaks" => breaks(line, run, &mut valid, &mut possible_completions, manager),
+            "run" => {
+                if run {
+                    manager.set_acquire(Acquire::Run);
+                    println!("Running");
+                }
+            }
+            "stop" => {
+                if run {
+                    manager.set_acquire(Acquire::Stop);
+                    println!("Stopped");
+                }
+            }
+            "sg" | "single" => single(line, run, &mut valid, &mut possible_completions, manager),
+            "ex" | "export" => export(line, run, &mut valid, &mut possible_completions, manager),
+            "rp" | "replay" => replay(line, run, &mut valid, &mut possible_completions, manager),
+            "lg" | "log" => log(line, run, &mut valid, &mut possible_completions, manager),
+            "wh" | "where" => where_from(line, run, &mut valid, &mut possible_completions, manager),
             &_ => {
                 if run {
                     println!("Invalid Command: {:?}", cmd)
@@ -29,6 +65,21 @@ pub fn parse(line: &str, run: bool, manager: &mut SignalManager) -> LineState {
                     possible_completions.push(String::from("select"));
                     possible_completions.push(String::from("bind"));
                     possible_completions.push(String::from("free"));
+                    possible_completions.push(String::from("clock"));
+                    possible_completions.push(String::from("window"));
+                    possible_completions.push(String::from("history"));
+                    possible_completions.push(String::from("memory"));
+                    possible_completions.push(String::from("health"));
+                    possible_completions.push(String::from("queue"));
+                    possible_completions.push(String::from("continue"));
+                    possible_completions.push(String::from("breaks"));
+                    possible_completions.push(String::from("run"));
+                    possible_completions.push(String::from("stop"));
+                    possible_completions.push(String::from("single"));
+                    possible_completions.push(String::from("replay"));
+                    possible_completions.push(String::from("export"));
+                    possible_completions.push(String::from("log"));
+                    possible_completions.push(String::from("where"));
                 }
             }
         }
@@ -192,6 +243,537 @@ fn free(cmd: &str, run: bool, valid: &mut bool, pc: &mut Vec<String>, manager: &
     }
 }
 
+// Choose whether signals are plotted against the time given by the producer or the time lines were received
+fn clock(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let mut c = Clock::Device;
+    if bits.len() > 1 {
+        match bits[1] {
+            "device" => c = Clock::Device,
+            "receive" => c = Clock::Received,
+            &_ => {
+                *valid = false;
+                pc.push(String::from("device"));
+                pc.push(String::from("receive"));
+                return;
+            }
+        }
+    }
+    let sigs = select_signals(
+        if bits.len() > 2 { &bits[2..] } else { &[] },
+        valid,
+        pc,
+        manager,
+    );
+    if !*valid {
+        return;
+    }
+    if run {
+        for s in sigs {
+            manager
+                .get_signal(&s)
+                .expect(
+                    "This vec can only consist of clones of the key strings from the signals map",
+                )
+                .set_clock(c);
+            println!("Set {:?} to {:?} time", s, c);
+        }
+    }
+}
+
+// Show only the last seconds of signals plotted against time and scroll with new points, go back to following them
+// after panning away, or show everything again with off. Signals bound on x share their window
+fn window(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let window = match bits.get(1) {
+        Some(&"off") => Some(None),
+        Some(&"follow") => None,
+        Some(w) => match w.trim_end_matches('s').parse::<f64>() {
+            Ok(secs) if secs > 0. => Some(Some(secs * 1e6)),
+            _ => {
+                *valid = false;
+                return;
+            }
+        },
+        None => {
+            *valid = false;
+            pc.push(String::from("off"));
+            pc.push(String::from("follow"));
+            return;
+        }
+    };
+    let sigs = select_signals(
+        if bits.len() > 2 { &bits[2..] } else { &[] },
+        valid,
+        pc,
+        manager,
+    );
+    if !*valid || !run {
+        return;
+    }
+    for s in sigs {
+        let view = manager
+            .get_signal(&s)
+            .expect("This vec can only consist of clones of the key strings from the signals map")
+            .get_view();
+        match window {
+            Some(w) => view.set_window(w),
+            None => view.set_follow(true),
+        }
+    }
+    match window {
+        Some(Some(w)) => println!("Showing the last {}s", w / 1e6),
+        Some(None) => println!("Showing everything"),
+        None => println!("Following the newest points"),
+    }
+}
+
+// Limit how many points signals keep, by count or by seconds such as 30s, or keep everything with off. Without
+// signals new signals get the limit as well
+fn history(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let history = match bits.get(1).map(|b| b.parse::<History>()) {
+        Some(Ok(h)) => h,
+        _ => {
+            *valid = false;
+            pc.push(String::from("off"));
+            return;
+        }
+    };
+    let sigs = select_signals(
+        if bits.len() > 2 { &bits[2..] } else { &[] },
+        valid,
+        pc,
+        manager,
+    );
+    if !*valid || !run {
+        return;
+    }
+    if bits.len() == 2 {
+        manager.history = history;
+    }
+    for s in sigs {
+        manager
+            .get_signal(&s)
+            .expect("This vec can only consist of clones of the key strings from the signals map")
+            .set_history(history);
+        println!("Set history of {:?} to {:?}", s, history);
+    }
+}
+
+// Limit the memory points of every signal take together, the oldest are written to disk past it. Without a size print
+// how much is used
+fn memory(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let limit = match bits.get(1).map(|b| spill::parse_limit(b)) {
+        None => None,
+        Some(Ok(l)) => Some(l),
+        Some(Err(_)) => {
+            *valid = false;
+            pc.push(String::from("off"));
+            return;
+        }
+    };
+    if !run {
+        return;
+    }
+    if let Some(l) = limit {
+        manager.memory.set_limit(l);
+        manager.spill();
+    }
+    let budget = &manager.memory;
+    println!(
+        "Points take {:.1}MB of {}",
+        budget.used() as f64 / (1 << 20) as f64,
+        budget.limit().map_or(String::from("no limit"), |l| format!(
+            "{:.1}MB",
+            l as f64 / (1 << 20) as f64
+        ))
+    );
+}
+
+// Print how many bad lines and points signals have received and the last thing that was wrong, or clear that record.
+// Names that only had errors so far are included
+fn health(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let clear = bits.len() > 1 && bits[1] == "clear";
+    if !clear && bits.len() == 2 && "clear".starts_with(bits[1]) {
+        pc.push(String::from("clear"));
+    }
+    let first = if clear { 2 } else { 1 };
+    let args = if bits.len() > first {
+        &bits[first..]
+    } else {
+        &[]
+    };
+    let sigs = select_signals(args, valid, pc, manager);
+    if !*valid {
+        return;
+    }
+    if run {
+        for s in sigs {
+            let health = manager
+                .get_signal(&s)
+                .expect(
+                    "This vec can only consist of clones of the key strings from the signals map",
+                )
+                .get_health_mut();
+            if clear {
+                health.clear();
+                println!("Cleared health of {:?}", s);
+            } else {
+                match health.last_error {
+                    Some(ref e) => println!(
+                        "{:?}: {:?}, {} errors, last: {}",
+                        s, health.state, health.errors, e
+                    ),
+                    None => println!("{:?}: {:?}", s, health.state),
+                }
+            }
+        }
+        let inverse = args.first().map_or(true, |a| *a == "!");
+        let unplotted = manager.unplotted();
+        let names: Vec<String> = unplotted
+            .keys()
+            .filter(|n| args.contains(&n.as_str()) != inverse)
+            .cloned()
+            .collect();
+        for s in names {
+            if clear {
+                unplotted.remove(&s);
+                println!("Cleared health of {:?}", s);
+            } else if let Some(ref e) = unplotted[&s].last_error {
+                println!(
+                    "{:?}: no points yet, {} errors, last: {}",
+                    s, unplotted[&s].errors, e
+                );
+            }
+        }
+    }
+}
+
+// Print how much input is waiting to be parsed and plotted and how much has been dropped, by producer
+fn queue(manager: &SignalManager) {
+    for (name, pending, dropped) in manager.queues.snapshot() {
+        println!("{}: {} lines queued, {} dropped", name, pending, dropped);
+    }
+}
+
+// Choose whether breakpoints stop the display or are only marked, or forget the marks of past breakpoints
+fn breaks(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    if bits.len() != 2 {
+        *valid = false;
+        pc.push(String::from("on"));
+        pc.push(String::from("off"));
+        pc.push(String::from("clear"));
+        return;
+    }
+    match bits[1] {
+        "on" | "off" => {
+            if run {
+                manager.break_on = bits[1] == "on";
+                println!("Breakpoints {}", bits[1]);
+            }
+        }
+        "clear" => {
+            if run {
+                manager.clear_breaks();
+            }
+        }
+        &_ => {
+            *valid = false;
+            pc.push(String::from("on"));
+            pc.push(String::from("off"));
+            pc.push(String::from("clear"));
+        }
+    }
+}
+
+// Capture once and stop: single [seconds] [signal rise|fall level]. Without a trigger the capture starts with the
+// next point, the window defaults to a second
+fn single(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let mut bits = &cmd.split_whitespace().collect::<Vec<&str>>()[1..];
+    let mut window = 1.;
+    if let Some(w) = bits.first().and_then(|b| b.parse::<f64>().ok()) {
+        window = w;
+        bits = &bits[1..];
+    }
+    let trigger = match bits.len() {
+        0 => None,
+        3 => {
+            let rising = match bits[1] {
+                "rise" => true,
+                "fall" => false,
+                _ => {
+                    *valid = false;
+                    pc.push(String::from("rise"));
+                    pc.push(String::from("fall"));
+                    return;
+                }
+            };
+            let level = match bits[2].parse::<f64>() {
+                Ok(l) => l,
+                Err(_) => {
+                    *valid = false;
+                    return;
+                }
+            };
+            Some(Trigger::new(String::from(bits[0]), level, rising))
+        }
+        n => {
+            *valid = false;
+            if n == 1 {
+                pc.extend(manager.get_names().cloned());
+            } else {
+                pc.push(String::from("rise"));
+                pc.push(String::from("fall"));
+            }
+            return;
+        }
+    };
+    if window <= 0. {
+        *valid = false;
+        return;
+    }
+    if run {
+        match trigger {
+            Some(ref t) => println!(
+                "Single {}s capture armed on {:?} {} {}",
+                window,
+                t.signal,
+                if t.rising {
+                    "rising through"
+                } else {
+                    "falling through"
+                },
+                t.level
+            ),
+            None => println!("Single {}s capture started", window),
+        }
+        manager.set_acquire(Acquire::Single(Single::new(trigger, window * 1e6)));
+    }
+}
+
+// Control a replay: pause, play, seek to a time in seconds (relative with a sign) or change its speed
+fn replay(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let player = match manager.player {
+        Some(ref p) => p,
+        None => {
+            *valid = false;
+            if run {
+                println!("Not replaying, start with --replay FILE");
+            }
+            return;
+        }
+    };
+    let control = match (bits.get(1), bits.get(2)) {
+        (Some(&"pause"), None) => Control::Pause,
+        (Some(&"play"), None) => Control::Play,
+        (Some(&"seek"), Some(t)) => match t.parse::<f64>() {
+            Ok(s) if t.starts_with('+') || t.starts_with('-') => {
+                Control::Seek(player.position() + s * 1e6)
+            }
+            Ok(s) => Control::Seek(s * 1e6),
+            Err(_) => {
+                *valid = false;
+                return;
+            }
+        },
+        (Some(&"speed"), Some(s)) => match s.parse::<Speed>() {
+            Ok(s) => Control::Speed(s),
+            Err(_) => {
+                *valid = false;
+                pc.push(String::from("real"));
+                pc.push(String::from("fast"));
+                return;
+            }
+        },
+        _ => {
+            *valid = false;
+            let options = ["pause", "play", "seek", "speed"];
+            pc.extend(options.iter().map(|o| String::from(*o)));
+            return;
+        }
+    };
+    if run {
+        player.send(control);
+    }
+}
+
+// Write the points of signals to a file: export all|visible FILE [signals], csv unless the file ends in .json
+fn export(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let visible = match bits.get(1) {
+        Some(&"all") => false,
+        Some(&"visible") => true,
+        _ => {
+            *valid = false;
+            pc.push(String::from("all"));
+            pc.push(String::from("visible"));
+            return;
+        }
+    };
+    let path = match bits.get(2) {
+        Some(p) => *p,
+        None => {
+            *valid = false;
+            return;
+        }
+    };
+    let sigs = select_signals(
+        if bits.len() > 3 { &bits[3..] } else { &[] },
+        valid,
+        pc,
+        manager,
+    );
+    if !*valid || !run {
+        return;
+    }
+    let area = if visible { Some(manager.area) } else { None };
+    let mut signals = Vec::new();
+    for s in sigs {
+        let sig = manager
+            .get_signal(&s)
+            .expect("This vec can only consist of clones of the key strings from the signals map");
+        match sig.export(area) {
+            Some(rows) => signals.push((s, rows)),
+            None => println!("{:?} has no points to export", s),
+        }
+    }
+    signals.sort_by(|a, b| a.0.cmp(&b.0));
+    match export::write(path, &signals) {
+        Ok(n) => println!(
+            "Exported {} points of {} signals to {}",
+            n,
+            signals.len(),
+            path
+        ),
+        Err(e) => println!("{}: {}", path, e),
+    }
+}
+
+// Show or hide the log pane, or only show lines containing some text. Filter without text shows every line again
+fn log(cmd: &str, run: bool, valid: &mut bool, pc: &mut Vec<String>, manager: &mut SignalManager) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let options = ["show", "hide", "filter"];
+    match bits.get(1) {
+        Some(&"show") | Some(&"hide") if bits.len() == 2 => {
+            if run {
+                manager.log.visible = bits[1] == "show";
+            }
+        }
+        Some(&"filter") => {
+            if run {
+                // Everything after the word filter, spaces and all
+                let text = cmd.splitn(2, "filter").nth(1).unwrap_or("").trim();
+                manager.log.filter = String::from(text);
+                manager.log.scroll = 0;
+            }
+        }
+        _ => {
+            *valid = false;
+            pc.extend(options.iter().map(|o| String::from(*o)));
+        }
+    }
+}
+
+// Print the line of input that each point under the cursor was read from
+fn where_from(
+    cmd: &str,
+    run: bool,
+    valid: &mut bool,
+    pc: &mut Vec<String>,
+    manager: &mut SignalManager,
+) {
+    let bits = cmd.split_whitespace().collect::<Vec<&str>>();
+    let sigs = select_signals(
+        if bits.len() > 1 { &bits[1..] } else { &[] },
+        valid,
+        pc,
+        manager,
+    );
+    if !*valid || !run {
+        return;
+    }
+    let under = manager.under_cursor.clone();
+    if under.is_empty() {
+        println!("No point under the cursor");
+    }
+    for (name, idx) in under {
+        if !sigs.contains(&name) {
+            continue;
+        }
+        let source = manager.get_signal(&name).and_then(|s| s.source(idx));
+        if let Some((line, t)) = source {
+            match manager.sources.get(line, t) {
+                Some(l) => println!("{:?}: line {} at {:.6}s: {}", name, line, t / 1e6, l.text),
+                None => println!(
+                    "{:?}: line {} at {:.6}s, start with --source-lines to keep its text",
+                    name,
+                    line,
+                    t / 1e6
+                ),
+            }
+        }
+    }
+}
+
 //TODO: take pc and provide
 fn select_signals(
     bits: &[&str],
@@ -0,0 +1,424 @@
+// Reading rows of delimited values (csv, tsv, whitespace separated) where every column becomes its own signal
+use ingest::{MsgPoint, Name, ParseError, Parsed, PointType};
+use std::sync::Arc;
+
+#[derive(Debug, Default, Clone)]
+pub struct CsvSettings {
+    // Every line that is not a scope message is a row, the first row names the columns if it is not all numbers.
+    // Otherwise a table starts with the first line made up entirely of numbers, the line before it names the columns
+    // if it has as many fields and none are numbers (split on whitespace only with a delimiter given). From then on
+    // lines split by the table's delimiter that are mostly numbers are rows, and reported when they do not fit
+    pub strict: bool,
+    pub delimiter: Option<char>, // Guessed from the row when not given, ' ' means any run of whitespace
+    pub columns: Vec<String>,    // Column names given up front instead of by a header row
+    pub x_column: Option<String>, // Name or index of the column plotted along x for all other columns
+}
+
+pub struct Delimited {
+    settings: CsvSettings,
+    delimiter: Option<char>,
+    columns: Vec<String>,
+    names: Vec<Name>, // Signal name of every column seen so far, built once rather than per row
+    x_index: Option<usize>,
+    seen_row: bool,
+    header: Option<(char, Vec<String>)>, // Last line passed through that could name the columns of a table after it
+}
+
+impl Delimited {
+    pub fn new(settings: CsvSettings) -> Delimited {
+        let mut d = Delimited {
+            delimiter: settings.delimiter,
+            columns: settings.columns.clone(),
+            names: Vec::new(),
+            x_index: None,
+            seen_row: false,
+            header: None,
+            settings,
+        };
+        d.find_x();
+        d
+    }
+
+    // None if the line is not a row and should be passed through
+    pub fn read(
+        &mut self,
+        line: &str,
+        timestamp: f64,
+        ln: usize,
+    ) -> Option<Result<Parsed, ParseError>> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() {
+            return None;
+        }
+        let delim = self.delimiter.unwrap_or_else(|| guess_delimiter(line));
+        let fields = split_fields(line, delim);
+
+        let mut values = Vec::with_capacity(fields.len());
+        let mut bad = None;
+        for (i, f) in fields.iter().enumerate() {
+            let f = f.trim();
+            if f.is_empty() {
+                values.push(None); // Missing value, nothing is plotted for this column on this row
+            } else if let Ok(v) = f.parse::<f64>() {
+                values.push(Some(v));
+            } else {
+                if bad.is_none() {
+                    bad = Some((i, String::from(f)));
+                }
+                values.push(None);
+            }
+        }
+
+        let numbers = values.iter().filter(|v| v.is_some()).count();
+        if !self.settings.strict {
+            let row = if self.seen_row {
+                let filled = fields.iter().filter(|f| !f.trim().is_empty()).count();
+                fields.len() > 1 && numbers > 0 && numbers * 2 >= filled
+            } else {
+                bad.is_none() && numbers > 0
+            };
+            if !row {
+                let names = fields.len() > 1
+                    && numbers == 0
+                    && fields.iter().all(|f| !f.trim().is_empty())
+                    && (delim != ' ' || self.settings.delimiter.is_some());
+                self.header = if names {
+                    Some((
+                        delim,
+                        fields.iter().map(|f| String::from(f.trim())).collect(),
+                    ))
+                } else {
+                    None
+                };
+                return None;
+            }
+        }
+        if self.delimiter.is_none() {
+            self.delimiter = Some(delim);
+        }
+        let first = !self.seen_row;
+        self.seen_row = true;
+        if let Some((d, names)) = self.header.take() {
+            if first && self.columns.is_empty() && d == delim && names.len() == values.len() {
+                self.columns = names;
+                self.find_x();
+            }
+        }
+
+        if let Some((i, value)) = bad {
+            if first && self.columns.is_empty() {
+                // Header row
+                self.columns = fields.iter().map(|f| String::from(f.trim())).collect();
+                self.find_x();
+                return Some(Ok(Parsed::Points(Vec::new())));
+            }
+            return Some(Err(ParseError::InvalidNumber {
+                name: self.column_name(i),
+                value,
+            }));
+        }
+
+        if first && self.columns.is_empty() {
+            // No header, the first row fixes how many columns there are
+            self.columns = (0..values.len()).map(|i| self.column_name(i)).collect();
+            self.find_x();
+        }
+        if !self.columns.is_empty() && self.columns.len() != values.len() {
+            return Some(Err(ParseError::MalformedRow {
+                names: self.columns.clone(),
+                expected: self.columns.len(),
+                found: values.len(),
+            }));
+        }
+
+        let x_index = self.x_index;
+        let x = x_index.map(|i| values.get(i).cloned().unwrap_or(None));
+        let mut points = Vec::with_capacity(values.len());
+        for (i, v) in values.iter().enumerate() {
+            if Some(i) == x_index {
+                continue;
+            }
+            let v = match *v {
+                Some(v) => v,
+                None => continue,
+            };
+            let (ty, px, py) = match x {
+                Some(Some(x)) => (PointType::D2, x, v),
+                Some(None) => continue, // Row has no x so its values can not be placed
+                None => (PointType::D1, v, f64::NAN),
+            };
+            points.push(MsgPoint {
+                name: self.point_name(i),
+                line_number: ln,
+                timestamp,
+                device_time: f64::NAN,
+                ty,
+                x: px,
+                y: py,
+                z: f64::NAN,
+                values: Vec::new(),
+                shape: (0, 0),
+            });
+        }
+        Some(Ok(Parsed::Points(points)))
+    }
+
+    fn column_name(&self, i: usize) -> String {
+        match self.columns.get(i) {
+            Some(c) => c.clone(),
+            None => format!("csv.{}", i),
+        }
+    }
+
+    fn point_name(&mut self, i: usize) -> Name {
+        while self.names.len() <= i {
+            let name = self.column_name(self.names.len());
+            self.names.push(Arc::from(name));
+        }
+        self.names[i].clone()
+    }
+
+    // Without known columns the x column can only be given by index. Called whenever the columns change
+    fn find_x(&mut self) {
+        self.names.clear();
+        self.x_index = self.settings.x_column.as_ref().and_then(|x| {
+            self.columns
+                .iter()
+                .position(|c| c == x)
+                .or_else(|| x.parse::<usize>().ok())
+        });
+    }
+}
+
+fn guess_delimiter(line: &str) -> char {
+    for d in ['\t', ',', ';'].iter() {
+        if line.contains(*d) {
+            return *d;
+        }
+    }
+    ' '
+}
+
+// Splits a row on delim, fields may be wrapped in double quotes in which case they can contain the delimiter and "" is
+// read as a single quote
+fn split_fields(line: &str, delim: char) -> Vec<String> {
+    let whitespace = delim == ' ';
+    let line = if whitespace { line.trim() } else { line };
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' && field.trim().is_empty() {
+            field.clear();
+            quoted = true;
+        } else if (whitespace && c.is_whitespace()) || c == delim {
+            fields.push(field);
+            field = String::new();
+            if whitespace {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict() -> CsvSettings {
+        CsvSettings {
+            strict: true,
+            ..CsvSettings::default()
+        }
+    }
+
+    fn points(d: &mut Delimited, line: &str) -> Vec<(String, f64, f64)> {
+        match d.read(line, 0., 0) {
+            Some(Ok(Parsed::Points(p))) => p
+                .iter()
+                .map(|p| (String::from(&*p.name), p.x, p.y))
+                .collect(),
+            r => panic!("{:?} gave {:?}", line, r),
+        }
+    }
+
+    fn names(d: &mut Delimited, line: &str) -> Vec<String> {
+        points(d, line).into_iter().map(|p| p.0).collect()
+    }
+
+    fn error(d: &mut Delimited, line: &str) -> ParseError {
+        match d.read(line, 0., 0) {
+            Some(Err(e)) => e,
+            r => panic!("{:?} gave {:?}", line, r),
+        }
+    }
+
+    #[test]
+    fn numbers_only_without_strict() {
+        let mut d = Delimited::new(CsvSettings::default());
+        assert!(d.read("some text", 0., 0).is_none());
+        assert!(d.read("   \n", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1,2\n"), vec!["csv.0", "csv.1"]);
+        assert_eq!(names(&mut d, "3,4"), vec!["csv.0", "csv.1"]);
+        assert!(d.read("some text", 0., 0).is_none());
+        assert!(d.read("took 5 ms", 0., 0).is_none());
+        assert!(d.read("a,b", 0., 0).is_none());
+    }
+
+    #[test]
+    fn header_without_strict() {
+        let mut d = Delimited::new(CsvSettings::default());
+        assert!(d.read("time,rpm", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1,2"), vec!["time", "rpm"]);
+    }
+
+    #[test]
+    fn header_must_come_right_before_and_fit() {
+        let mut d = Delimited::new(CsvSettings::default());
+        assert!(d.read("time,rpm", 0., 0).is_none());
+        assert!(d.read("starting", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1,2"), vec!["csv.0", "csv.1"]);
+        let mut d = Delimited::new(CsvSettings::default());
+        assert!(d.read("time,rpm,temp", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1,2"), vec!["csv.0", "csv.1"]);
+        let mut d = Delimited::new(CsvSettings::default());
+        assert!(d.read("time;rpm", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1,2"), vec!["csv.0", "csv.1"]);
+    }
+
+    #[test]
+    fn whitespace_header_needs_a_delimiter() {
+        let mut d = Delimited::new(CsvSettings::default());
+        assert!(d.read("starting up", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1 2"), vec!["csv.0", "csv.1"]);
+        let mut d = Delimited::new(CsvSettings {
+            delimiter: Some(' '),
+            ..CsvSettings::default()
+        });
+        assert!(d.read("time rpm", 0., 0).is_none());
+        assert_eq!(names(&mut d, "1 2"), vec!["time", "rpm"]);
+    }
+
+    #[test]
+    fn rows_that_do_not_fit_without_strict() {
+        let mut d = Delimited::new(CsvSettings::default());
+        d.read("a,b", 0., 0);
+        points(&mut d, "1,2");
+        match error(&mut d, "1,2,3") {
+            ParseError::MalformedRow {
+                expected, found, ..
+            } => assert_eq!((expected, found), (2, 3)),
+            e => panic!("{:?}", e),
+        }
+        match error(&mut d, "1,x") {
+            ParseError::InvalidNumber { name, value } => {
+                assert_eq!((name.as_str(), value.as_str()), ("b", "x"))
+            }
+            e => panic!("{:?}", e),
+        }
+        assert!(d.read("x,y,3", 0., 0).is_none()); // Mostly text
+        assert!(d.read("1\t2", 0., 0).is_none()); // Not split by the table's delimiter
+    }
+
+    #[test]
+    fn header() {
+        let mut d = Delimited::new(strict());
+        assert!(points(&mut d, "time,rpm").is_empty());
+        assert_eq!(names(&mut d, "1,2"), vec!["time", "rpm"]);
+    }
+
+    #[test]
+    fn given_columns() {
+        let mut d = Delimited::new(CsvSettings {
+            columns: vec![String::from("a"), String::from("b")],
+            ..strict()
+        });
+        assert_eq!(names(&mut d, "1;2"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn malformed_row() {
+        let mut d = Delimited::new(strict());
+        points(&mut d, "a,b");
+        match d.read("1,2,3", 0., 0) {
+            Some(Err(ParseError::MalformedRow {
+                expected, found, ..
+            })) => {
+                assert_eq!((expected, found), (2, 3))
+            }
+            r => panic!("{:?}", r),
+        }
+        match d.read("1,x", 0., 0) {
+            Some(Err(ParseError::InvalidNumber { name, value })) => {
+                assert_eq!((name.as_str(), value.as_str()), ("b", "x"))
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn missing_values() {
+        let mut d = Delimited::new(strict());
+        points(&mut d, "a,b,c");
+        assert_eq!(names(&mut d, "1,,3"), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn x_column() {
+        let mut d = Delimited::new(CsvSettings {
+            x_column: Some(String::from("t")),
+            ..strict()
+        });
+        points(&mut d, "t,a,b");
+        assert_eq!(
+            points(&mut d, "10,1,2"),
+            vec![(String::from("a"), 10., 1.), (String::from("b"), 10., 2.)]
+        );
+        assert!(points(&mut d, ",1,2").is_empty()); // Nowhere to put them without x
+    }
+
+    #[test]
+    fn x_column_by_index() {
+        let mut d = Delimited::new(CsvSettings {
+            x_column: Some(String::from("1")),
+            ..CsvSettings::default()
+        });
+        assert_eq!(points(&mut d, "5 7"), vec![(String::from("csv.0"), 7., 5.)]);
+    }
+
+    #[test]
+    fn delimiters() {
+        assert_eq!(guess_delimiter("1\t2,3"), '\t');
+        assert_eq!(guess_delimiter("1,2;3"), ',');
+        assert_eq!(guess_delimiter("1;2"), ';');
+        assert_eq!(guess_delimiter("1 2"), ' ');
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            split_fields("a,\"b,c\",\"d\"\"e\"", ','),
+            vec!["a", "b,c", "d\"e"]
+        );
+        assert_eq!(split_fields("  1   2\t3 ", ' '), vec!["1", "2", "3"]);
+        assert_eq!(split_fields("1,,", ','), vec!["1", "", ""]);
+    }
+}
@@ -2,11 +2,15 @@ extern crate color_set;
 extern crate glium;
 
 use self::color_set::Color;
-use super::signal::{Axes, PickData, Point, Range, RangedDeque};
+use super::signal::{Axes, GraphNode, PickData, Point, Range, RangedDeque};
 use glium::Surface;
 use glium::VertexBuffer;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use std::time::Instant;
 
+// Positions in a buffer are relative to the origin of the buffer, so f32 keeps its precision for values far from zero
+// such as timestamps. The transform of each buffer carries the origin in f64, see Transform::rebase
 #[derive(Debug, Copy, Clone)]
 struct Vertex {
     position: [f32; 3],
@@ -14,7 +18,30 @@ struct Vertex {
 }
 implement_vertex!(Vertex, position, color);
 
-fn make_vertex<T>(color: &Color, pt: &Point<T>) -> Vertex
+// A vertex before it is placed in a buffer
+#[derive(Debug, Copy, Clone)]
+struct Place {
+    position: [f64; 3],
+    color: [f32; 3],
+}
+
+impl Place {
+    fn origin(&self) -> (f64, f64) {
+        (self.position[0], self.position[1])
+    }
+    fn relative_to(&self, origin: (f64, f64)) -> Vertex {
+        Vertex {
+            position: [
+                (self.position[0] - origin.0) as f32,
+                (self.position[1] - origin.1) as f32,
+                self.position[2] as f32,
+            ],
+            color: self.color,
+        }
+    }
+}
+
+fn make_vertex<T>(color: &Color, pt: &Point<T>) -> Place
 where
     T: Axes<T>,
 {
@@ -25,36 +52,53 @@ where
     } else {
         1.
     }; //TODO: z should be d[1]/max z
-    let v = Vertex {
-        position: [(x) as f32, (y) as f32, z as f32],
+    let v = Place {
+        position: [x, y, z],
         color: [color.0, color.1, color.2],
     };
     return v;
 }
 
+// Kept in f64 until it is handed to a shader, after being rebased onto the origin of what is drawn
 #[derive(Debug, Clone, Copy)]
 pub struct Transform {
-    pub dx: f32,
-    pub dy: f32,
-    pub sx: f32,
-    pub sy: f32,
-    pub sz: f32,
+    pub dx: f64,
+    pub dy: f64,
+    pub sx: f64,
+    pub sy: f64,
+    pub sz: f64,
+}
+
+impl Transform {
+    // Same mapping for positions given relative to origin
+    pub fn rebase(&self, origin: (f64, f64)) -> Transform {
+        Transform {
+            dx: self.dx + origin.0 * self.sx,
+            dy: self.dy + origin.1 * self.sy,
+            ..*self
+        }
+    }
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (x * self.sx + self.dx, y * self.sy + self.dy)
+    }
 }
 
 impl<'a> From<&'a Transform> for [[f32; 4]; 4] {
     fn from(tf: &Transform) -> Self {
         [
-            [tf.sx, 0.0, 0.0, 0.0],
-            [0.0, tf.sy, 0.0, 0.0],
-            [0.0, 0.0, tf.sz, 0.0],
-            [tf.dx, tf.dy, 0.0, 1.0f32],
+            [tf.sx as f32, 0.0, 0.0, 0.0],
+            [0.0, tf.sy as f32, 0.0, 0.0],
+            [0.0, 0.0, tf.sz as f32, 0.0],
+            [tf.dx as f32, tf.dy as f32, 0.0, 1.0f32],
         ]
     }
 }
 
 struct VBOChunks {
-    vbos: VecDeque<VertexBuffer<Vertex>>,
+    vbos: VecDeque<(VertexBuffer<Vertex>, (f64, f64))>, // Along with the origin of each
+    last: Option<Place>, // Copied to the start of the next buffer when connected
     current_vbo_size: usize,
+    start: usize, // First vertex of the front buffer that is still drawn, the ones before it were popped
     connected: bool,
 }
 const VBO_SIZE: usize = 256;
@@ -62,11 +106,13 @@ impl VBOChunks {
     fn new(connected: bool) -> VBOChunks {
         VBOChunks {
             vbos: VecDeque::new(),
+            last: None,
             current_vbo_size: 0,
+            start: 0,
             connected,
         }
     }
-    fn push<T>(&mut self, v: Vertex, display: &glium::Display)
+    fn push<T>(&mut self, v: Place, display: &glium::Display)
     where
         T: Axes<T>,
     {
@@ -74,57 +120,118 @@ impl VBOChunks {
             match VertexBuffer::empty_dynamic(display, VBO_SIZE) {
                 Ok(mut vbo) => {
                     self.current_vbo_size = 0;
-                    if self.connected {
-                        //map back vertex from last buffer
-                        if let Some(t) = self.vbos.back() {
-                            let last_pt = t.slice(VBO_SIZE - 1..VBO_SIZE).unwrap().read().unwrap();
-                            vbo.as_mut_slice().slice(0..1).unwrap().write(&last_pt);
+                    let origin = v.origin();
+                    if self.connected && !self.vbos.is_empty() {
+                        if let Some(last) = self.last {
+                            let first = [last.relative_to(origin)];
+                            vbo.as_mut_slice().slice(0..1).unwrap().write(&first);
                             self.current_vbo_size += 1;
                         }
                     }
-                    self.vbos.push_back(vbo);
+                    self.vbos.push_back((vbo, origin));
+                    self.drop_popped();
                 }
                 Err(e) => println!("{:?}", e), //TODO: update signal health indicator, if out of vram start using ram?
             }
         }
         // println!("~.L@{:?}", self.vbos.len());
         // dbg!(self.current_vbo_size);
-        if let Some(vbo) = self.vbos.back_mut() {
+        if let Some(&mut (ref mut vbo, origin)) = self.vbos.back_mut() {
             vbo.as_mut_slice()
                 .slice(self.current_vbo_size..self.current_vbo_size + 1)
                 .unwrap()
-                .write(&[v]);
+                .write(&[v.relative_to(origin)]);
             self.current_vbo_size += 1;
+            self.last = Some(v);
             // println!("~.V@{:?}", self.current_vbo_size);
         }
     }
+    fn clear(&mut self) {
+        self.vbos.clear();
+        self.last = None;
+        self.current_vbo_size = 0;
+        self.start = 0;
+    }
+    fn pop_front(&mut self) {
+        self.start += 1;
+        self.drop_popped();
+    }
+    fn bytes(&self) -> usize {
+        self.vbos.len() * VBO_SIZE * size_of::<Vertex>()
+    }
+    // Front buffers every vertex of which was popped. The last buffer is kept as the next one copies its last vertex
+    fn drop_popped(&mut self) {
+        while self.vbos.len() > 1 && self.start >= VBO_SIZE {
+            self.vbos.pop_front();
+            // A connected buffer starts with a copy of the last vertex of the one before
+            self.start -= VBO_SIZE - if self.connected { 1 } else { 0 };
+        }
+    }
     fn draw<F>(&self, mut drawer: F)
     where
-        F: FnMut(glium::vertex::VertexBufferSlice<Vertex>) -> (),
+        F: FnMut(glium::vertex::VertexBufferSlice<Vertex>, (f64, f64)) -> (),
     {
         let mut c = 0;
-        for i in self.vbos.iter() {
+        for &(ref i, origin) in self.vbos.iter() {
             //Is there a performance hit for slicing everything?
-            let vb = i
-                .slice(
-                    0..if c < self.vbos.len() - 1 {
-                        VBO_SIZE
-                    } else {
-                        self.current_vbo_size
-                    },
-                )
-                .unwrap();
-            drawer(vb);
+            let start = if c == 0 { self.start } else { 0 };
+            let end = if c < self.vbos.len() - 1 {
+                VBO_SIZE
+            } else {
+                self.current_vbo_size
+            };
             c += 1;
+            if start >= end {
+                continue;
+            }
+            drawer(i.slice(start..end).unwrap(), origin);
         }
     }
 }
 
-//transform applied in shader, point x and y, unit scale x and y. allows draw style to select what point values are used for x and y (x might be time)
-fn point_pos(trans: Transform, x: f64, y: f64, us_x: f64, us_y: f64) -> (f32, f32) {
-    let x2 = (x * us_x) as f32; // TODO: these need to work according to unit scale implementation, find a nice way for signal to handle this
-    let y2 = (y * us_y) as f32;
-    (x2 * trans.sx + trans.dx, y2 * trans.sy + trans.dy)
+//transform applied in shader, point x and y. allows draw style to select what point values are used for x and y (x might be time)
+fn point_pos(trans: Transform, x: f64, y: f64) -> (f32, f32) {
+    let (x, y) = trans.apply(x, y);
+    (x as f32, y as f32)
+}
+
+// Vertices that only live for one frame
+fn draw_once(
+    xy: &[(f64, f64)],
+    color: &Color,
+    trans: &Transform,
+    target: &mut glium::Frame,
+    display: &glium::Display,
+    program: &glium::Program,
+    primitive: glium::index::PrimitiveType,
+    params: &glium::DrawParameters,
+) {
+    let origin = match xy.first() {
+        Some(&o) => o,
+        None => return,
+    };
+    let vertices: Vec<Vertex> = xy
+        .iter()
+        .map(|&(x, y)| {
+            Place {
+                position: [x, y, 1.],
+                color: [color.0, color.1, color.2],
+            }
+            .relative_to(origin)
+        })
+        .collect();
+    let vb = match VertexBuffer::new(display, &vertices) {
+        Ok(vb) => vb,
+        Err(e) => return println!("{:?}", e),
+    };
+    let t: [[f32; 4]; 4] = (&trans.rebase(origin)).into();
+    let uniforms = uniform! {
+        matrix: t
+    };
+    let indices = glium::index::NoIndices(primitive);
+    target
+        .draw(&vb, &indices, program, &uniforms, params)
+        .unwrap()
 }
 
 fn find_min<T, F>(points: &RangedDeque<T>, cmp: F) -> (Option<usize>, f32)
@@ -135,7 +242,7 @@ where
     let mut min_val = 2.0;
     let mut min_idx = None;
     for (i, pt) in points.iter().enumerate() {
-        let d = cmp(pt);
+        let d = cmp(&pt);
         if d < min_val {
             min_val = d;
             min_idx = Some(i)
@@ -158,7 +265,7 @@ where
     let y = pt.axes[T::y()].clone().into();
     let mut z = String::new();
     if T::x() != T::timestamp() {
-        z.push_str(&format!("{:.*} ns, ", 3, ts))
+        z.push_str(&format!("{:.*} us, ", 3, ts))
     }
     if T::z() >= 0 {
         z.push_str(&format!("{:.*}", 3, T::z() as usize));
@@ -170,7 +277,7 @@ where
 }
 
 // List of Drawstyles defined in this file, used to ensure that all styles are provided as options from command line
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Styles {
     Scatter,
     Lines,
@@ -187,27 +294,37 @@ where
         points: &RangedDeque<T>,
         display: &glium::Display,
     );
+    // Drop every point pushed so far, used by signals that redraw from scratch
+    fn clear(&mut self);
+    // Drop the oldest point pushed, the signal dropped it from its history
+    fn pop_front(&mut self);
+    // Memory of the vertices of the points pushed, charged to the budget along with the points
+    fn bytes(&self) -> usize;
     fn draw(&self, trans: &Transform, target: &mut glium::Frame);
+    // Draw points given for this frame instead of those pushed, for signals that decimate what they show
+    fn draw_summary(
+        &self,
+        xy: &[(f64, f64)],
+        color: &Color,
+        trans: &Transform,
+        target: &mut glium::Frame,
+        display: &glium::Display,
+    );
     fn pick(
         &self,
         points: &RangedDeque<T>,
         mouse: (f32, f32),
         trans: Transform,
-        unit_scale: Vec<f64>,
         pick_thresh: f32,
     ) -> Option<PickData> {
         let mut t = trans.clone();
-        t.dx -= mouse.0;
-        t.dy -= mouse.1;
-        let ux = unit_scale[T::x()];
-        let uy = unit_scale[T::y()];
+        t.dx -= mouse.0 as f64;
+        t.dy -= mouse.1 as f64;
         let d = find_min(points, move |pt| {
             let (x, y) = point_pos(
                 t.clone(),
                 pt.axes[T::x()].clone().into(),
                 pt.axes[T::y()].clone().into(),
-                ux,
-                uy,
             );
             x.abs() + y.abs()
         });
@@ -220,8 +337,6 @@ where
                         trans,
                         pt.axes[T::x()].clone().into(),
                         pt.axes[T::y()].clone().into(),
-                        ux,
-                        uy,
                     ),
                 });
             }
@@ -245,16 +360,15 @@ pub struct Scatter {
     program: glium::Program,
 }
 
-impl Scatter {
-    pub fn new(display: &glium::Display) -> Scatter {
-        let source = glium::program::ProgramCreationInput::SourceCode {
-            tessellation_control_shader: None,
-            tessellation_evaluation_shader: None,
-            geometry_shader: None,
-            outputs_srgb: false,
-            uses_point_size: true,
-
-            vertex_shader: r##"
+fn scatter_program(display: &glium::Display) -> glium::Program {
+    let source = glium::program::ProgramCreationInput::SourceCode {
+        tessellation_control_shader: None,
+        tessellation_evaluation_shader: None,
+        geometry_shader: None,
+        outputs_srgb: false,
+        uses_point_size: true,
+
+        vertex_shader: r##"
 			    #version 140
 
 			    in vec3 position;
@@ -269,7 +383,7 @@ impl Scatter {
 			        gl_Position = matrix * vec4(position.xy, 0.0, 1.0);
 			    }
 				"##,
-            fragment_shader: r##"
+        fragment_shader: r##"
     		    #version 140
 
     		    in vec3 attr_color;
@@ -280,11 +394,16 @@ impl Scatter {
     		    }
 	    		"##,
 
-            transform_feedback_varyings: None,
-        };
+        transform_feedback_varyings: None,
+    };
+    glium::Program::new(display, source).unwrap()
+}
+
+impl Scatter {
+    pub fn new(display: &glium::Display) -> Scatter {
         Scatter {
             vbos: VBOChunks::new(false),
-            program: glium::Program::new(display, source).unwrap(),
+            program: scatter_program(display),
         }
     }
 }
@@ -302,14 +421,43 @@ where
     ) {
         self.vbos.push::<T>(make_vertex::<T>(color, pt), display);
     }
+    fn clear(&mut self) {
+        self.vbos.clear();
+    }
+    fn pop_front(&mut self) {
+        self.vbos.pop_front();
+    }
+    fn bytes(&self) -> usize {
+        self.vbos.bytes()
+    }
+    fn draw_summary(
+        &self,
+        xy: &[(f64, f64)],
+        color: &Color,
+        trans: &Transform,
+        target: &mut glium::Frame,
+        display: &glium::Display,
+    ) {
+        let primitive = glium::index::PrimitiveType::Points;
+        let params = Default::default();
+        draw_once(
+            xy,
+            color,
+            trans,
+            target,
+            display,
+            &self.program,
+            primitive,
+            &params,
+        );
+    }
     fn draw(&self, trans: &Transform, target: &mut glium::Frame) {
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::Points);
-        let t: [[f32; 4]; 4] = trans.into();
-        let uniforms = uniform! {
-            matrix: t
-        };
-
-        self.vbos.draw(move |vb| {
+        self.vbos.draw(move |vb, origin| {
+            let t: [[f32; 4]; 4] = (&trans.rebase(origin)).into();
+            let uniforms = uniform! {
+                matrix: t
+            };
             target
                 .draw(vb, &indices, &self.program, &uniforms, &Default::default())
                 .unwrap()
@@ -322,13 +470,10 @@ pub struct Lines {
     program: glium::Program,
 }
 
-impl Lines {
-    pub fn new(display: &glium::Display) -> Lines {
-        Lines {
-            vbos: VBOChunks::new(true),
-            program: glium::Program::from_source(
-                display,
-                r##"
+fn lines_program(display: &glium::Display) -> glium::Program {
+    glium::Program::from_source(
+        display,
+        r##"
 			    #version 140
 
 			    in vec3 position;
@@ -342,7 +487,7 @@ impl Lines {
 			        gl_Position = matrix * vec4(position.xy, 0.0, 1.0);
 			    }
 			"##,
-                r##"
+        r##"
     		    #version 140
 
     		    in vec4 attr_color;
@@ -352,9 +497,16 @@ impl Lines {
     		        color = attr_color;
     		    }
     		"##,
-                None,
-            )
-            .unwrap(),
+        None,
+    )
+    .unwrap()
+}
+
+impl Lines {
+    pub fn new(display: &glium::Display) -> Lines {
+        Lines {
+            vbos: VBOChunks::new(true),
+            program: lines_program(display),
         }
     }
 }
@@ -372,22 +524,242 @@ where
     ) {
         self.vbos.push::<T>(make_vertex::<T>(color, pt), display);
     }
+    fn clear(&mut self) {
+        self.vbos.clear();
+    }
+    fn pop_front(&mut self) {
+        self.vbos.pop_front();
+    }
+    fn bytes(&self) -> usize {
+        self.vbos.bytes()
+    }
+    fn draw_summary(
+        &self,
+        xy: &[(f64, f64)],
+        color: &Color,
+        trans: &Transform,
+        target: &mut glium::Frame,
+        display: &glium::Display,
+    ) {
+        let primitive = glium::index::PrimitiveType::LineStrip;
+        let params = glium::DrawParameters {
+            line_width: Some(4.),
+            ..Default::default()
+        };
+        draw_once(
+            xy,
+            color,
+            trans,
+            target,
+            display,
+            &self.program,
+            primitive,
+            &params,
+        );
+    }
     fn draw(&self, trans: &Transform, target: &mut glium::Frame) {
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::LineStrip);
-        let t: [[f32; 4]; 4] = trans.into();
-        let uniforms = uniform! {
-            matrix: t
-        };
-
         let params = glium::DrawParameters {
             line_width: Some(4.),
             ..Default::default()
         };
 
-        self.vbos.draw(move |vb| {
+        self.vbos.draw(move |vb, origin| {
+            let t: [[f32; 4]; 4] = (&trans.rebase(origin)).into();
+            let uniforms = uniform! {
+                matrix: t
+            };
             target
                 .draw(vb, &indices, &self.program, &uniforms, &params)
                 .unwrap()
         });
     }
 }
+
+const NODE_SIZE: f32 = 12.;
+const HIGHLIGHT_SECS: f64 = 2.; // How long a node stays lit after it sends a message
+
+// Directed graphs are laid out in layers, each node is placed one layer below the closest node that lists it as a child.
+// Nodes that only appear inside cycles are treated as roots so that everything gets a position
+pub struct Graph {
+    nodes: glium::Program,
+    edges: glium::Program,
+    positions: HashMap<u64, (f64, f64)>,
+}
+
+impl Graph {
+    pub fn new(display: &glium::Display) -> Graph {
+        Graph {
+            nodes: scatter_program(display),
+            edges: lines_program(display),
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn layout(&mut self, graph: &BTreeMap<u64, GraphNode>) {
+        let children: HashSet<u64> = graph
+            .values()
+            .flat_map(|n| n.children.iter().cloned())
+            .collect();
+        let mut roots: VecDeque<u64> = graph
+            .keys()
+            .filter(|id| !children.contains(id))
+            .cloned()
+            .collect();
+
+        let mut layers: Vec<Vec<u64>> = Vec::new();
+        let mut depth: HashMap<u64, usize> = HashMap::new();
+        let mut unplaced = graph.keys();
+        loop {
+            let mut queue = VecDeque::new();
+            if let Some(r) = roots.pop_front() {
+                queue.push_back((r, 0));
+            } else if let Some(r) = unplaced.find(|id| !depth.contains_key(id)) {
+                queue.push_back((*r, 0));
+            } else {
+                break;
+            }
+            while let Some((id, d)) = queue.pop_front() {
+                if depth.contains_key(&id) {
+                    continue;
+                }
+                depth.insert(id, d);
+                if layers.len() <= d {
+                    layers.push(Vec::new());
+                }
+                layers[d].push(id);
+                if let Some(node) = graph.get(&id) {
+                    for c in node.children.iter() {
+                        queue.push_back((*c, d + 1));
+                    }
+                }
+            }
+        }
+
+        self.positions.clear();
+        for (d, layer) in layers.iter().enumerate() {
+            let offset = (layer.len() as f64 - 1.) / 2.;
+            for (i, id) in layer.iter().enumerate() {
+                self.positions.insert(*id, (i as f64 - offset, -(d as f64)));
+            }
+        }
+    }
+
+    // Padded so that a lone node or a single layer still has some area to fill
+    pub fn get_range(&self) -> Range {
+        let mut min = vec![-0.5f64, -0.5];
+        let mut max = vec![0.5f64, 0.5];
+        for &(x, y) in self.positions.values() {
+            min[0] = min[0].min(x - 0.5);
+            min[1] = min[1].min(y - 0.5);
+            max[0] = max[0].max(x + 0.5);
+            max[1] = max[1].max(y + 0.5);
+        }
+        Range { min, max }
+    }
+
+    pub fn draw(
+        &self,
+        graph: &BTreeMap<u64, GraphNode>,
+        color: &Color,
+        trans: &Transform,
+        target: &mut glium::Frame,
+        display: &glium::Display,
+    ) {
+        let now = Instant::now();
+        let mut nodes = Vec::with_capacity(graph.len());
+        let mut edges = Vec::new();
+        let dim = [color.0 * 0.5, color.1 * 0.5, color.2 * 0.5];
+        for (id, node) in graph.iter() {
+            let pos = match self.positions.get(id) {
+                Some(p) => *p,
+                None => continue,
+            };
+            let lit = node.updated.map_or(0., |t| {
+                let age = now.duration_since(t);
+                let age = age.as_secs() as f64 + age.subsec_nanos() as f64 * 1e-9;
+                (1. - age / HIGHLIGHT_SECS).max(0.)
+            }) as f32;
+            nodes.push(Vertex {
+                position: [pos.0 as f32, pos.1 as f32, NODE_SIZE * (1. + lit)],
+                color: [
+                    color.0 + (1. - color.0) * lit,
+                    color.1 + (1. - color.1) * lit,
+                    color.2 + (1. - color.2) * lit,
+                ],
+            });
+            for c in node.children.iter() {
+                if let Some(cpos) = self.positions.get(c) {
+                    edges.push(Vertex {
+                        position: [pos.0 as f32, pos.1 as f32, 1.],
+                        color: dim,
+                    });
+                    edges.push(Vertex {
+                        position: [cpos.0 as f32, cpos.1 as f32, 1.],
+                        color: dim,
+                    });
+                }
+            }
+        }
+
+        let t: [[f32; 4]; 4] = trans.into();
+        let uniforms = uniform! {
+            matrix: t
+        };
+        let params = glium::DrawParameters {
+            line_width: Some(2.),
+            ..Default::default()
+        };
+        match VertexBuffer::new(display, &edges) {
+            Ok(vb) => target
+                .draw(
+                    &vb,
+                    &glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+                    &self.edges,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap(),
+            Err(e) => println!("{:?}", e),
+        }
+        match VertexBuffer::new(display, &nodes) {
+            Ok(vb) => target
+                .draw(
+                    &vb,
+                    &glium::index::NoIndices(glium::index::PrimitiveType::Points),
+                    &self.nodes,
+                    &uniforms,
+                    &Default::default(),
+                )
+                .unwrap(),
+            Err(e) => println!("{:?}", e),
+        }
+    }
+
+    // Index of the picked node is its position in the graph ordering
+    pub fn pick(
+        &self,
+        graph: &BTreeMap<u64, GraphNode>,
+        mouse: (f32, f32),
+        trans: Transform,
+        pick_thresh: f32,
+    ) -> Option<PickData> {
+        let mut best: Option<(f32, PickData)> = None;
+        for (i, id) in graph.keys().enumerate() {
+            if let Some(&(x, y)) = self.positions.get(id) {
+                let screen_pos = point_pos(trans, x, y);
+                let d = (screen_pos.0 - mouse.0).abs() + (screen_pos.1 - mouse.1).abs();
+                if d <= pick_thresh && best.as_ref().map_or(true, |b| d < b.0) {
+                    best = Some((
+                        d,
+                        PickData {
+                            index: i,
+                            screen_pos,
+                        },
+                    ));
+                }
+            }
+        }
+        best.map(|b| b.1)
+    }
+}
@@ -0,0 +1,67 @@
+// Writing points out of the signals, as csv or as json when the file name ends in .json. Every point is a row of the
+// signal it belongs to, the time it is plotted at in seconds and its values, values a signal does not have are empty
+// in csv and null in json
+extern crate serde_json;
+
+use self::serde_json::{Map, Value};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+pub type Row = [f64; 4]; // Time in us, x, y, z
+
+pub fn write(path: &str, signals: &[(String, Vec<Row>)]) -> io::Result<usize> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let rows = if path.ends_with(".json") {
+        json(&mut out, signals)?
+    } else {
+        csv(&mut out, signals)?
+    };
+    out.flush()?;
+    Ok(rows)
+}
+
+fn csv<W: Write>(out: &mut W, signals: &[(String, Vec<Row>)]) -> io::Result<usize> {
+    writeln!(out, "signal,timestamp,x,y,z")?;
+    let mut count = 0;
+    for (name, rows) in signals {
+        let name = if name.contains(',') || name.contains('"') {
+            format!("\"{}\"", name.replace('"', "\"\""))
+        } else {
+            name.clone()
+        };
+        for row in rows {
+            write!(out, "{},{}", name, row[0] / 1e6)?;
+            for v in row[1..].iter() {
+                if v.is_nan() {
+                    write!(out, ",")?;
+                } else {
+                    write!(out, ",{}", v)?;
+                }
+            }
+            writeln!(out)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn json<W: Write>(out: &mut W, signals: &[(String, Vec<Row>)]) -> io::Result<usize> {
+    let number = |v: f64| serde_json::Number::from_f64(v).map_or(Value::Null, Value::Number);
+    let mut points = Vec::new();
+    for (name, rows) in signals {
+        for row in rows {
+            let mut point = Map::new();
+            point.insert(String::from("signal"), Value::String(name.clone()));
+            point.insert(String::from("timestamp"), number(row[0] / 1e6));
+            for (key, v) in ["x", "y", "z"].iter().zip(row[1..].iter()) {
+                point.insert(String::from(*key), number(*v));
+            }
+            points.push(Value::Object(point));
+        }
+    }
+    let count = points.len();
+    serde_json::to_writer(&mut *out, &Value::Array(points))?;
+    writeln!(out)?;
+    Ok(count)
+}
@@ -0,0 +1,735 @@
+// Line protocol parsing. Nothing in here knows about channels, stdout or glium so that producers output can be
+// validated on its own and the parser reused by other front ends.
+use delimited::{CsvSettings, Delimited};
+use json::{self, JsonSettings};
+use rules::Rule;
+use scan::{self, Message, Numbers, Pairs, Time};
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn duration2us(dur: &Duration) -> f64 {
+    (dur.as_secs() * 1_000_000 + dur.subsec_nanos() as u64 / 1000) as f64
+}
+
+// Signal names are shared between every point of a signal instead of allocated per point, see Interner
+pub type Name = Arc<str>;
+
+#[derive(Debug, Clone)]
+pub struct MsgPoint {
+    pub name: Name,
+    pub line_number: usize,
+    pub timestamp: f64,   // Time the line was received in us
+    pub device_time: f64, // Time given by the producer in us, f64::NAN if the line had none
+    pub ty: PointType,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub values: Vec<f64>, // Payload of a list message in row major order, or the child ids of a node message
+    pub shape: (usize, usize), // Rows and columns of a list message
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointType {
+    BreakPoint,
+    D1,
+    D2,
+    D3,
+    List,
+    Node, // Node id is carried in x
+}
+
+// Result of successfully reading a single line of input
+#[derive(Debug)]
+pub enum Parsed {
+    Point(MsgPoint),
+    Points(Vec<MsgPoint>), // Lines that carry several signals at once, may be empty if the line only held names
+    Passthrough,           // Line is not meant for us and should be handed on untouched
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    // A value could not be converted to a number
+    InvalidNumber {
+        name: String,
+        value: String,
+    },
+    // List payload does not fill its declared shape
+    InvalidShape {
+        name: String,
+        rows: usize,
+        cols: usize,
+        len: usize,
+    },
+    // Delimited row with the wrong number of fields, names are the columns of the table
+    MalformedRow {
+        names: Vec<String>,
+        expected: usize,
+        found: usize,
+    },
+    // Binary sample whose body does not match the layout its flags describe
+    InvalidFrame {
+        name: String,
+        len: usize,
+        expected: usize,
+    },
+    // ~.name@ followed by neither values nor key=value pairs
+    Malformed {
+        name: String,
+        text: String,
+    },
+    // More values than the three a point has
+    TooManyValues {
+        name: String,
+        found: usize,
+    },
+}
+
+impl ParseError {
+    // Names of the signals the bad message was addressed to
+    pub fn signal_names(&self) -> Vec<&str> {
+        match self {
+            ParseError::InvalidNumber { name, .. } => vec![name],
+            ParseError::InvalidShape { name, .. } => vec![name],
+            ParseError::InvalidFrame { name, .. } => vec![name],
+            ParseError::Malformed { name, .. } => vec![name],
+            ParseError::TooManyValues { name, .. } => vec![name],
+            ParseError::MalformedRow { names, .. } => names.iter().map(|n| n.as_str()).collect(),
+        }
+    }
+
+    // Prefixes the signal names with the connection the line came from
+    pub fn tag(&mut self, origin: &str) {
+        match self {
+            ParseError::InvalidNumber { name, .. }
+            | ParseError::InvalidShape { name, .. }
+            | ParseError::InvalidFrame { name, .. }
+            | ParseError::Malformed { name, .. }
+            | ParseError::TooManyValues { name, .. } => *name = format!("{}/{}", origin, name),
+            ParseError::MalformedRow { names, .. } => {
+                for n in names.iter_mut() {
+                    *n = format!("{}/{}", origin, n);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidNumber { name, value } => {
+                write!(f, "{}: could not read {:?} as a number", name, value)
+            }
+            ParseError::InvalidShape {
+                name,
+                rows,
+                cols,
+                len,
+            } => write!(
+                f,
+                "{}: {} values do not fit a {}x{} list",
+                name, len, rows, cols
+            ),
+            ParseError::MalformedRow {
+                names,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {} fields but found {}",
+                names.join(","),
+                expected,
+                found
+            ),
+            ParseError::InvalidFrame {
+                name,
+                len,
+                expected,
+            } => write!(
+                f,
+                "{}: binary sample of {} bytes where {} were expected",
+                name, len, expected
+            ),
+            ParseError::Malformed { name, text } => write!(
+                f,
+                "{}: could not read {:?} as values or key=value pairs",
+                name, text
+            ),
+            ParseError::TooManyValues { name, found } => {
+                write!(f, "{}: {} values where at most 3 fit a point", name, found)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// Which signals are plotted and which are also handed on to stdout, names may contain * and ? wildcards
+#[derive(Debug, Default)]
+pub struct ReaderSettings {
+    pub capture: Vec<String>, // When not empty only these signals are plotted, everything else is passed through
+    pub pass: Vec<String>,    // Plotted as normal but also passed through
+    pub csv: CsvSettings,
+    pub json: JsonSettings,
+    pub tag_origin: bool, // Signal names are prefixed with the connection they came from, origin/name
+    pub rules: Vec<Rule>, // Tried on lines the parser finds nothing in
+    pub keep_source: bool, // Text of lines that produced points is sent on with them
+}
+
+impl ReaderSettings {
+    pub fn captures(&self, name: &str) -> bool {
+        self.capture.is_empty() || self.capture.iter().any(|p| glob_match(p, name))
+    }
+    pub fn passes(&self, name: &str) -> bool {
+        self.pass.iter().any(|p| glob_match(p, name))
+    }
+}
+
+// * matches any run of characters and ? any single character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    let mut star = None; // Position of the last * seen and the name position it was tried against
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            // Let the last * swallow one more character and try again
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Default)]
+pub struct Interner {
+    names: HashSet<Name>,
+    tagged: HashMap<Name, Name>, // Names with their origin in front, an interner only ever sees one origin
+}
+
+impl Interner {
+    pub fn get(&mut self, name: &str) -> Name {
+        if let Some(n) = self.names.get(name) {
+            return n.clone();
+        }
+        let n: Name = Arc::from(name);
+        self.names.insert(n.clone());
+        n
+    }
+
+    // origin/name, made once per signal rather than for every point
+    pub fn tagged(&mut self, origin: &str, name: &Name) -> Name {
+        if let Some(n) = self.tagged.get(name) {
+            return n.clone();
+        }
+        let n = self.get(&format!("{}/{}", origin, name));
+        self.tagged.insert(name.clone(), n.clone());
+        n
+    }
+}
+
+pub struct Parser {
+    names: Interner,
+    key: String, // Scratch space for building the names of keyed values
+    csv: Delimited,
+    json: JsonSettings,
+    last_device: HashMap<Name, f64>, // Last producer time seen per signal, base for relative times
+}
+
+impl Parser {
+    pub fn new(csv: CsvSettings, json: JsonSettings) -> Parser {
+        Parser {
+            names: Interner::default(),
+            key: String::new(),
+            csv: Delimited::new(csv),
+            json,
+            last_device: HashMap::new(),
+        }
+    }
+
+    // Names handed out by the parser, for anything else making points from the same input
+    pub fn names(&mut self) -> &mut Interner {
+        &mut self.names
+    }
+
+    // Timestamp is the time the line was received, ln is its position in the input stream
+    pub fn parse(
+        &mut self,
+        line: &str,
+        timestamp: &Duration,
+        ln: usize,
+    ) -> Result<Parsed, ParseError> {
+        let ts = duration2us(timestamp);
+
+        let (msg, time) = match scan::message(line) {
+            Some(m) => m,
+            None => {
+                return match json::read(&self.json, &mut self.names, line, ts, ln) {
+                    Some(r) => r,
+                    None => match self.csv.read(line, ts, ln) {
+                        Some(r) => r,
+                        None => Ok(Parsed::Passthrough), //Not a message
+                    },
+                };
+            }
+        };
+        let name = self.names.get(msg.name());
+        let device_time = self.device_time(&name, time)?;
+        let mut point = match msg {
+            Message::Keyed { pairs, .. } => {
+                let mut points = self.handle_keyed(&name, pairs, ts, ln)?;
+                for p in points.iter_mut() {
+                    p.device_time = device_time;
+                }
+                return Ok(Parsed::Points(points));
+            }
+            Message::Values { count, .. } if count > 3 => Err(ParseError::TooManyValues {
+                name: String::from(&*name),
+                found: count,
+            }),
+            Message::Values { values, count, .. } => handle_values(name, &values[..count], ts, ln),
+            Message::List { shape, payload, .. } => handle_list(name, shape, payload, ts, ln),
+            Message::Node { id, children, .. } => handle_node(name, id, children, ts, ln),
+            Message::Break { .. } => Ok(point(name, PointType::BreakPoint, ts, ln, [f64::NAN; 3])),
+            Message::Malformed { payload, .. } => Err(ParseError::Malformed {
+                name: String::from(&*name),
+                text: String::from(payload),
+            }),
+        }?;
+        point.device_time = device_time;
+        Ok(Parsed::Point(point))
+    }
+
+    // Producer time of a message in us, f64::NAN if it did not give one
+    fn device_time(&mut self, name: &Name, time: Option<Time>) -> Result<f64, ParseError> {
+        let t = match time {
+            Some(t) => t,
+            None => return Ok(f64::NAN),
+        };
+        let mut time = number(name, t.value)?
+            * match t.unit {
+                "ns" => 1e-3,
+                "us" => 1.,
+                "ms" => 1e3,
+                _ => 1e6,
+            };
+        if t.relative {
+            time += self.last_device.get(name).cloned().unwrap_or(0.);
+        }
+        self.last_device.insert(name.clone(), time);
+        Ok(time)
+    }
+
+    // ~.name@key=value,key=value,... every key becomes its own signal name.key, all sharing the line's times
+    fn handle_keyed(
+        &mut self,
+        name: &str,
+        pairs: &str,
+        timestamp: f64,
+        ln: usize,
+    ) -> Result<Vec<MsgPoint>, ParseError> {
+        let mut points = Vec::new();
+        for (key, value) in Pairs::new(pairs) {
+            self.key.clear();
+            let _ = write!(self.key, "{}.{}", name, key);
+            let name = self.names.get(&self.key);
+            let x = number(&name, value)?;
+            points.push(point(
+                name,
+                PointType::D1,
+                timestamp,
+                ln,
+                [x, f64::NAN, f64::NAN],
+            ));
+        }
+        Ok(points)
+    }
+}
+
+fn number(name: &str, value: &str) -> Result<f64, ParseError> {
+    value.parse::<f64>().map_err(|_| ParseError::InvalidNumber {
+        name: String::from(name),
+        value: String::from(value),
+    })
+}
+
+fn point(name: Name, ty: PointType, timestamp: f64, ln: usize, xyz: [f64; 3]) -> MsgPoint {
+    MsgPoint {
+        name,
+        line_number: ln,
+        timestamp,
+        device_time: f64::NAN,
+        ty,
+        x: xyz[0],
+        y: xyz[1],
+        z: xyz[2],
+        values: Vec::new(),
+        shape: (0, 0),
+    }
+}
+
+// ~.name@x[,y[,z]]
+fn handle_values(
+    name: Name,
+    values: &[&str],
+    timestamp: f64,
+    ln: usize,
+) -> Result<MsgPoint, ParseError> {
+    let mut v = [f64::NAN; 3];
+    for (i, s) in values.iter().enumerate() {
+        v[i] = number(&name, s)?;
+    }
+    let ty = match values.len() {
+        1 => PointType::D1,
+        2 => PointType::D2,
+        _ => PointType::D3,
+    };
+    Ok(point(name, ty, timestamp, ln, v))
+}
+
+// ~#name#rows,cols@(...) the shape is optional, without it each top level parenthesised group is taken as a row
+fn handle_list(
+    name: Name,
+    shape: Option<(&str, &str)>,
+    payload: &str,
+    timestamp: f64,
+    ln: usize,
+) -> Result<MsgPoint, ParseError> {
+    let mut values = Vec::new();
+    let mut groups = Vec::new(); // number of values in each parenthesised group
+    let mut depth = 0;
+    let mut numbers = Numbers::new(payload);
+    for (between, n) in numbers.by_ref() {
+        track_groups(between, &mut depth, &mut groups, values.len());
+        values.push(number(&name, n)?);
+    }
+    track_groups(numbers.tail(), &mut depth, &mut groups, values.len());
+    let len = values.len();
+
+    let shape = match shape {
+        Some((r, c)) => {
+            let dim = |s: &str| {
+                s.parse::<usize>().map_err(|_| ParseError::InvalidNumber {
+                    name: String::from(&*name),
+                    value: String::from(s),
+                })
+            };
+            (dim(r)?, dim(c)?)
+        }
+        None => {
+            let rows = groups.len();
+            if depth == 0
+                && rows > 0
+                && groups.iter().all(|g| *g == groups[0])
+                && rows * groups[0] == len
+            {
+                (rows, groups[0])
+            } else {
+                (1, len)
+            }
+        }
+    };
+    if len == 0 || shape.0.checked_mul(shape.1) != Some(len) {
+        return Err(ParseError::InvalidShape {
+            name: String::from(&*name),
+            rows: shape.0,
+            cols: shape.1,
+            len,
+        });
+    }
+
+    let mut p = point(name, PointType::List, timestamp, ln, [f64::NAN; 3]);
+    p.values = values;
+    p.shape = shape;
+    Ok(p)
+}
+
+// Follows the brackets between two values of a list payload. While a top level group is open its entry in groups holds
+// the value count at which it started, once closed it holds the number of values inside it
+fn track_groups(between: &str, depth: &mut usize, groups: &mut Vec<usize>, count: usize) {
+    for c in between.chars() {
+        match c {
+            '(' => {
+                if *depth == 0 {
+                    groups.push(count);
+                }
+                *depth += 1;
+            }
+            ')' if *depth > 0 => {
+                *depth -= 1;
+                if *depth == 0 {
+                    let start = groups.pop().unwrap_or(count);
+                    groups.push(count - start);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// ~%name@id[child, child, ...]
+fn handle_node(
+    name: Name,
+    id: &str,
+    children: &str,
+    timestamp: f64,
+    ln: usize,
+) -> Result<MsgPoint, ParseError> {
+    let parse_id = |s: &str| {
+        s.parse::<u64>().map_err(|_| ParseError::InvalidNumber {
+            name: String::from(&*name),
+            value: String::from(s),
+        })
+    };
+    let node = parse_id(id)?;
+    let mut ids = Vec::new();
+    for c in children.split(|c: char| c == ',' || c.is_whitespace()) {
+        if !c.is_empty() {
+            ids.push(parse_id(c)? as f64);
+        }
+    }
+    let mut p = point(
+        name,
+        PointType::Node,
+        timestamp,
+        ln,
+        [node as f64, f64::NAN, f64::NAN],
+    );
+    p.values = ids;
+    Ok(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> Parser {
+        Parser::new(CsvSettings::default(), JsonSettings::default())
+    }
+
+    // Lines are received 1.5s in and are line 7 of their stream
+    fn parse_with(p: &mut Parser, line: &str) -> Result<Parsed, ParseError> {
+        p.parse(line, &Duration::from_millis(1500), 7)
+    }
+
+    fn parse(line: &str) -> Result<Parsed, ParseError> {
+        parse_with(&mut parser(), line)
+    }
+
+    fn one(line: &str) -> MsgPoint {
+        match parse(line) {
+            Ok(Parsed::Point(p)) => p,
+            r => panic!("{:?} gave {:?}", line, r),
+        }
+    }
+
+    fn many(line: &str) -> Vec<MsgPoint> {
+        match parse(line) {
+            Ok(Parsed::Points(p)) => p,
+            r => panic!("{:?} gave {:?}", line, r),
+        }
+    }
+
+    fn passes(line: &str) -> bool {
+        matches!(parse(line), Ok(Parsed::Passthrough))
+    }
+
+    #[test]
+    fn values() {
+        let p = one("~.a@1.5");
+        assert_eq!((&*p.name, p.ty, p.x), ("a", PointType::D1, 1.5));
+        assert!(p.y.is_nan() && p.z.is_nan());
+        let p = one("~.a@1,2\n");
+        assert_eq!((p.ty, p.x, p.y), (PointType::D2, 1., 2.));
+        let p = one("~.a@1, 2 ,-3e2");
+        assert_eq!((p.ty, p.x, p.y, p.z), (PointType::D3, 1., 2., -300.));
+    }
+
+    #[test]
+    fn values_inside_text() {
+        let p = one("motor says ~.speed@12 and more");
+        assert_eq!((&*p.name, p.x), ("speed", 12.));
+    }
+
+    #[test]
+    fn name_runs_to_last_at() {
+        assert_eq!(&*one("~.a@b@3").name, "a@b");
+    }
+
+    #[test]
+    fn too_many_values() {
+        let err = ParseError::TooManyValues {
+            name: String::from("a"),
+            found: 4,
+        };
+        assert_eq!(parse("~.a@1,2,3,4").unwrap_err(), err);
+    }
+
+    #[test]
+    fn keyed() {
+        let p = many("~.m@rpm=5, temp = -2");
+        let got: Vec<(&str, f64)> = p.iter().map(|p| (&*p.name, p.x)).collect();
+        assert_eq!(got, vec![("m.rpm", 5.), ("m.temp", -2.)]);
+        assert!(p
+            .iter()
+            .all(|p| p.ty == PointType::D1 && p.line_number == 7));
+    }
+
+    #[test]
+    fn keyed_time() {
+        let p = many("~.m@rpm=5,temp=1 t=2ms");
+        assert!(p.iter().all(|p| p.device_time == 2000.));
+    }
+
+    #[test]
+    fn time_is_not_a_key() {
+        let err = ParseError::Malformed {
+            name: String::from("x"),
+            text: String::from("t=5"),
+        };
+        assert_eq!(parse("~.x@t=5").unwrap_err(), err);
+    }
+
+    #[test]
+    fn malformed() {
+        let err = ParseError::Malformed {
+            name: String::from("m"),
+            text: String::from("rpm=fast"),
+        };
+        assert_eq!(parse("~.m@rpm=fast\n").unwrap_err(), err);
+        match parse("~.m@") {
+            Err(ParseError::Malformed { ref text, .. }) if text.is_empty() => {}
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn list() {
+        let p = one("~#l#2,3@(1,2,3)(4,5,6)");
+        assert_eq!(p.ty, PointType::List);
+        assert_eq!(p.shape, (2, 3));
+        assert_eq!(p.values, vec![1., 2., 3., 4., 5., 6.]);
+        assert_eq!(one("~#l#@(1,2)(3,4)(5,6)").shape, (3, 2));
+        assert_eq!(one("~#l#@1,2,3").shape, (1, 3));
+        assert_eq!(one("~#l#@(1,2)(3)").shape, (1, 3)); // Uneven groups are one row
+    }
+
+    #[test]
+    fn list_bad_shape() {
+        let err = ParseError::InvalidShape {
+            name: String::from("l"),
+            rows: 2,
+            cols: 2,
+            len: 3,
+        };
+        assert_eq!(parse("~#l#2,2@(1,2,3)").unwrap_err(), err);
+    }
+
+    #[test]
+    fn node() {
+        let p = one("~%g@3[1, 2]");
+        assert_eq!((p.ty, p.x), (PointType::Node, 3.));
+        assert_eq!(p.values, vec![1., 2.]);
+        assert!(one("~%g@4[]").values.is_empty());
+        match parse("~%g@99999999999999999999999[1]") {
+            Err(ParseError::InvalidNumber { ref name, .. }) if name == "g" => {}
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn breakpoint() {
+        let p = one("~!stop here");
+        assert_eq!((&*p.name, p.ty), ("stop", PointType::BreakPoint));
+        assert_eq!(one("~!stop@t=3s").device_time, 3e6);
+    }
+
+    #[test]
+    fn receive_time() {
+        let p = one("~.a@1");
+        assert_eq!((p.timestamp, p.line_number), (1.5e6, 7));
+        assert!(p.device_time.is_nan());
+    }
+
+    #[test]
+    fn device_time_units() {
+        assert_eq!(one("~.a@1 t=5").device_time, 5e6);
+        assert_eq!(one("~.a@1,t=5s").device_time, 5e6);
+        assert_eq!(one("~.a@1 t=5ms").device_time, 5e3);
+        assert_eq!(one("~.a@1 t=5us").device_time, 5.);
+        assert_eq!(one("~.a@1 t=5000ns").device_time, 5.);
+        assert_eq!(one("~.a@1 t=1.5e-3s").device_time, 1500.);
+    }
+
+    #[test]
+    fn relative_device_time() {
+        let mut p = parser();
+        let mut time = |line: &str| match parse_with(&mut p, line) {
+            Ok(Parsed::Point(p)) => p.device_time,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(time("~.a@1 t=5"), 5e6);
+        assert_eq!(time("~.a@2 t=+2s"), 7e6);
+        assert_eq!(time("~.a@3 t=+1ms"), 7.001e6);
+        assert_eq!(time("~.b@1 t=+1"), 1e6); // Every signal counts from its own last time
+    }
+
+    #[test]
+    fn passthrough() {
+        assert!(passes("hello world\n"));
+        assert!(passes(""));
+        assert!(passes("a ~ tilde ~. and ~# without messages"));
+        assert!(passes("{\"not\": \"numbers\"}"));
+    }
+
+    #[test]
+    fn json_and_csv_fallback() {
+        let p = one("{\"sig\":\"m\",\"x\":1,\"y\":2}");
+        assert_eq!((&*p.name, p.ty, p.x, p.y), ("m", PointType::D2, 1., 2.));
+        let p = many("1,2");
+        let got: Vec<(&str, f64)> = p.iter().map(|p| (&*p.name, p.x)).collect();
+        assert_eq!(got, vec![("csv.0", 1.), ("csv.1", 2.)]);
+    }
+
+    #[test]
+    fn errors_name_their_signal() {
+        let mut err = parse("~.x@t=5").unwrap_err();
+        err.tag("10.0.0.1:5000");
+        assert_eq!(err.signal_names(), vec!["10.0.0.1:5000/x"]);
+    }
+
+    #[test]
+    fn tagged_names_are_shared() {
+        let mut names = Interner::default();
+        let x = names.get("x");
+        let a = names.tagged("10.0.0.1:5000", &x);
+        let b = names.tagged("10.0.0.1:5000", &x);
+        assert_eq!(&*a, "10.0.0.1:5000/x");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(Arc::ptr_eq(&a, &names.get("10.0.0.1:5000/x")));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("motor.*", "motor.rpm"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("m?t*r", "motor"));
+        assert!(glob_match("*.rpm", "a.b.rpm"));
+        assert!(!glob_match("motor", "motors"));
+        assert!(!glob_match("?", ""));
+    }
+}
@@ -0,0 +1,316 @@
+// Reading JSON objects, one per line. An object naming its signal and giving x (and optionally y and z) becomes a
+// single point, e.g. {"sig":"motor","x":1.2,"y":3.4}. Any other object has each of its numeric fields plotted as its
+// own signal, nested objects and arrays are flattened into dotted names, e.g. {"motor":{"rpm":5}} is motor.rpm
+use ingest::{Interner, MsgPoint, Name, ParseError, Parsed, PointType};
+use serde_json::{Map, Value};
+
+use std::fmt::Write;
+
+// Fields of an object that have a meaning to scope, nested fields are given with dotted paths
+#[derive(Debug, Clone)]
+pub struct JsonSettings {
+    pub signal: String,
+    pub x: String,
+    pub y: String,
+    pub z: String,
+    pub time: String, // Producer time in seconds
+}
+
+impl Default for JsonSettings {
+    fn default() -> JsonSettings {
+        JsonSettings {
+            signal: String::from("sig"),
+            x: String::from("x"),
+            y: String::from("y"),
+            z: String::from("z"),
+            time: String::from("t"),
+        }
+    }
+}
+
+impl JsonSettings {
+    // Changes the field mapping from key=field pairs, key being one of signal, x, y, z or t
+    pub fn map(&mut self, pair: &str) -> Result<(), String> {
+        let mut kv = pair.splitn(2, '=');
+        let (key, field) = match (kv.next(), kv.next()) {
+            (Some(k), Some(f)) if !f.is_empty() => (k, String::from(f)),
+            _ => return Err(format!("Expected key=field, got {:?}", pair)),
+        };
+        match key {
+            "signal" | "sig" => self.signal = field,
+            "x" => self.x = field,
+            "y" => self.y = field,
+            "z" => self.z = field,
+            "t" | "time" => self.time = field,
+            _ => return Err(format!("Unknown json field {:?}", key)),
+        }
+        Ok(())
+    }
+}
+
+// None if the line is not a JSON object and should be handled as text
+pub fn read(
+    settings: &JsonSettings,
+    names: &mut Interner,
+    line: &str,
+    timestamp: f64,
+    ln: usize,
+) -> Option<Result<Parsed, ParseError>> {
+    let text = line.trim();
+    if !text.starts_with('{') {
+        return None;
+    }
+    let obj = match serde_json::from_str::<Value>(text) {
+        Ok(Value::Object(obj)) => obj,
+        _ => return None,
+    };
+    let point = |name: Name| MsgPoint {
+        name,
+        line_number: ln,
+        timestamp,
+        device_time: f64::NAN,
+        ty: PointType::D1,
+        x: f64::NAN,
+        y: f64::NAN,
+        z: f64::NAN,
+        values: Vec::new(),
+        shape: (0, 0),
+    };
+
+    let sig = lookup(&obj, &settings.signal).and_then(|s| s.as_str());
+    let device_time = match lookup(&obj, &settings.time) {
+        Some(t) => match t.as_f64() {
+            Some(t) => t * 1e6,
+            None => return Some(Err(invalid(sig.unwrap_or(&settings.time), t))),
+        },
+        None => f64::NAN,
+    };
+
+    if let (Some(sig), Some(_)) = (sig, lookup(&obj, &settings.x)) {
+        let mut p = point(names.get(sig));
+        let mut v = Vec::with_capacity(3);
+        for field in [&settings.x, &settings.y, &settings.z].iter() {
+            match lookup(&obj, field) {
+                Some(n) => match n.as_f64() {
+                    Some(n) => v.push(n),
+                    None => return Some(Err(invalid(sig, n))),
+                },
+                None => break,
+            }
+        }
+        p.ty = match v.len() {
+            1 => PointType::D1,
+            2 => PointType::D2,
+            _ => PointType::D3,
+        };
+        p.x = v[0];
+        p.y = *v.get(1).unwrap_or(&f64::NAN);
+        p.z = *v.get(2).unwrap_or(&f64::NAN);
+        p.device_time = device_time;
+        return Some(Ok(Parsed::Point(p)));
+    }
+
+    // Every name is built in the same buffer and only allocated the first time it is seen
+    let mut fields = Vec::new();
+    let mut path = String::from(sig.unwrap_or(""));
+    let start = if path.is_empty() { 0 } else { path.len() + 1 };
+    let skip = [&settings.signal, &settings.time];
+    flatten(&obj, &mut path, start, &skip, names, &mut fields);
+    if fields.is_empty() {
+        return Some(Ok(Parsed::Passthrough)); // Nothing to plot
+    }
+    let points = fields
+        .into_iter()
+        .map(|(name, v)| {
+            let mut p = point(name);
+            p.x = v;
+            p.device_time = device_time;
+            p
+        })
+        .collect();
+    Some(Ok(Parsed::Points(points)))
+}
+
+fn invalid(name: &str, value: &Value) -> ParseError {
+    ParseError::InvalidNumber {
+        name: String::from(name),
+        value: value.to_string(),
+    }
+}
+
+fn lookup<'a>(obj: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let mut parts = path.split('.');
+    let mut v = obj.get(parts.next()?)?;
+    for part in parts {
+        v = match *v {
+            Value::Object(ref o) => o.get(part)?,
+            Value::Array(ref a) => a.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(v)
+}
+
+// Collects every numeric field under obj with its dotted name, fields whose path is in skip are left out. The path
+// starts at start in prefix, anything before it is the signal
+fn flatten(
+    obj: &Map<String, Value>,
+    prefix: &mut String,
+    start: usize,
+    skip: &[&String],
+    names: &mut Interner,
+    out: &mut Vec<(Name, f64)>,
+) {
+    for (k, v) in obj.iter() {
+        let len = prefix.len();
+        if len > 0 {
+            prefix.push('.');
+        }
+        prefix.push_str(k);
+        flatten_value(v, prefix, start, skip, names, out);
+        prefix.truncate(len);
+    }
+}
+
+fn flatten_value(
+    v: &Value,
+    prefix: &mut String,
+    start: usize,
+    skip: &[&String],
+    names: &mut Interner,
+    out: &mut Vec<(Name, f64)>,
+) {
+    if skip.iter().any(|s| **s == prefix[start..]) {
+        return;
+    }
+    match *v {
+        Value::Number(ref n) => {
+            if let Some(n) = n.as_f64() {
+                out.push((names.get(prefix), n));
+            }
+        }
+        Value::Object(ref o) => flatten(o, prefix, start, skip, names, out),
+        Value::Array(ref a) => {
+            for (i, v) in a.iter().enumerate() {
+                let len = prefix.len();
+                let _ = write!(prefix, ".{}", i);
+                flatten_value(v, prefix, start, skip, names, out);
+                prefix.truncate(len);
+            }
+        }
+        _ => {} // Strings, booleans and nulls are not plotted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn read_default(line: &str) -> Option<Result<Parsed, ParseError>> {
+        read(
+            &JsonSettings::default(),
+            &mut Interner::default(),
+            line,
+            1.,
+            2,
+        )
+    }
+
+    fn fields(line: &str) -> Vec<(String, f64)> {
+        match read_default(line) {
+            Some(Ok(Parsed::Points(p))) => {
+                p.iter().map(|p| (String::from(&*p.name), p.x)).collect()
+            }
+            r => panic!("{:?} gave {:?}", line, r),
+        }
+    }
+
+    #[test]
+    fn not_json() {
+        assert!(read_default("text").is_none());
+        assert!(read_default("{broken").is_none());
+        assert!(read_default("[1, 2]").is_none());
+    }
+
+    #[test]
+    fn point() {
+        match read_default("{\"sig\":\"m\",\"x\":1,\"y\":2,\"z\":3,\"t\":1.5}") {
+            Some(Ok(Parsed::Point(p))) => {
+                assert_eq!((&*p.name, p.ty), ("m", PointType::D3));
+                assert_eq!((p.x, p.y, p.z, p.device_time), (1., 2., 3., 1.5e6));
+                assert_eq!((p.timestamp, p.line_number), (1., 2));
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn flattened() {
+        let got = fields("{\"motor\":{\"rpm\":5,\"on\":true},\"v\":[1,2],\"s\":\"x\"}");
+        let want = vec![
+            (String::from("motor.rpm"), 5.),
+            (String::from("v.0"), 1.),
+            (String::from("v.1"), 2.),
+        ];
+        assert_eq!(got, want);
+        assert_eq!(
+            fields("{\"sig\":\"m\",\"a\":1}"),
+            vec![(String::from("m.a"), 1.)]
+        );
+    }
+
+    #[test]
+    fn names_are_shared() {
+        let mut names = Interner::default();
+        let mut first = |line: &str| match read(&JsonSettings::default(), &mut names, line, 0., 0) {
+            Some(Ok(Parsed::Points(p))) => p[0].name.clone(),
+            r => panic!("{:?}", r),
+        };
+        let a = first("{\"sig\":\"m\",\"v\":[1]}");
+        let b = first("{\"sig\":\"m\",\"v\":[2]}");
+        assert_eq!(&*a, "m.v.0");
+        assert!(Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn nothing_to_plot() {
+        match read_default("{\"s\":\"x\"}") {
+            Some(Ok(Parsed::Passthrough)) => {}
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        match read_default("{\"sig\":\"m\",\"x\":\"fast\"}") {
+            Some(Err(ParseError::InvalidNumber { name, value })) => {
+                assert_eq!((name.as_str(), value.as_str()), ("m", "\"fast\""))
+            }
+            r => panic!("{:?}", r),
+        }
+        assert!(matches!(
+            read_default("{\"t\":\"now\",\"a\":1}"),
+            Some(Err(ParseError::InvalidNumber { .. }))
+        ));
+    }
+
+    #[test]
+    fn mapped_fields() {
+        let mut s = JsonSettings::default();
+        s.map("sig=meta.name").unwrap();
+        s.map("x=data.0").unwrap();
+        assert!(s.map("w=a").is_err());
+        assert!(s.map("x=").is_err());
+        match read(
+            &s,
+            &mut Interner::default(),
+            "{\"meta\":{\"name\":\"m\"},\"data\":[4]}",
+            0.,
+            0,
+        ) {
+            Some(Ok(Parsed::Point(p))) => assert_eq!((&*p.name, p.x), ("m", 4.)),
+            r => panic!("{:?}", r),
+        }
+    }
+}
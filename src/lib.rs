@@ -0,0 +1,15 @@
+// Parts of scope that do not depend on a window, usable headless by other front ends
+extern crate crossbeam_channel as channel;
+extern crate regex;
+extern crate serde_json;
+
+pub mod binary;
+pub mod delimited;
+pub mod ingest;
+pub mod json;
+pub mod queue;
+pub mod rules;
+pub mod scan;
+pub mod session;
+pub mod source;
+pub mod spill;
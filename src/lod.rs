@@ -0,0 +1,324 @@
+// Min/max pyramid over the points of a signal plotted against time, so drawing and picking cost what fits on screen
+// rather than what was received. Level k has a bucket for every FANOUT^(k+1) points holding the lowest and highest
+// point among them, a level is added on top whenever the one below has more than FANOUT buckets. Points are numbered
+// in the order they were pushed and popped from the front, the signal looks up which numbers are within a window. A bucket that
+// reaches past the points asked for, or some of whose points were popped, is never used, the points under it that are
+// wanted are covered from the level below instead
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+const FANOUT: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Extreme {
+    pub seq: usize,
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    lo: Extreme,
+    hi: Extreme,
+}
+
+impl Bucket {
+    fn add(&mut self, e: Extreme) {
+        if e.y < self.lo.y {
+            self.lo = e;
+        }
+        if e.y > self.hi.y {
+            self.hi = e;
+        }
+    }
+    fn merge(&mut self, other: &Bucket) {
+        self.add(other.lo);
+        self.add(other.hi);
+    }
+}
+
+#[derive(Debug)]
+pub struct Pyramid {
+    levels: Vec<VecDeque<Bucket>>,
+    front: Vec<usize>, // Number of the front bucket of each level
+    len: usize,        // Points pushed, the number of the next one
+    live: usize,       // Number of the oldest point not popped
+}
+
+fn size(level: usize) -> usize {
+    FANOUT.pow(level as u32 + 1)
+}
+
+impl Default for Pyramid {
+    fn default() -> Pyramid {
+        Pyramid::new()
+    }
+}
+
+impl Pyramid {
+    pub fn new() -> Pyramid {
+        Pyramid {
+            levels: vec![VecDeque::new()],
+            front: vec![0],
+            len: 0,
+            live: 0,
+        }
+    }
+
+    pub fn push(&mut self, x: f64, y: f64) {
+        let e = Extreme {
+            seq: self.len,
+            x,
+            y,
+        };
+        self.len += 1;
+        for (k, level) in self.levels.iter_mut().enumerate() {
+            if e.seq / size(k) == self.front[k] + level.len() {
+                level.push_back(Bucket { lo: e, hi: e });
+            } else if let Some(b) = level.back_mut() {
+                b.add(e);
+            }
+        }
+        while self.levels.last().is_some_and(|l| l.len() > FANOUT) {
+            self.grow();
+        }
+    }
+
+    // Builds the level above the top one out of its buckets
+    fn grow(&mut self) {
+        let k = self.levels.len() - 1;
+        let front = self.front[k] / FANOUT;
+        let mut up: VecDeque<Bucket> = VecDeque::new();
+        for (i, b) in self.levels[k].iter().enumerate() {
+            if (self.front[k] + i) / FANOUT == front + up.len() {
+                up.push_back(*b);
+            } else if let Some(u) = up.back_mut() {
+                u.merge(b);
+            }
+        }
+        self.levels.push(up);
+        self.front.push(front);
+    }
+
+    pub fn pop_front(&mut self) {
+        self.live += 1;
+        for (k, level) in self.levels.iter_mut().enumerate() {
+            while !level.is_empty() && (self.front[k] + 1) * size(k) <= self.live {
+                level.pop_front();
+                self.front[k] += 1;
+            }
+        }
+    }
+
+    // Memory held by the buckets, charged to the budget along with the points
+    pub fn bytes(&self) -> usize {
+        self.levels.iter().map(|l| l.len()).sum::<usize>() * size_of::<Bucket>()
+    }
+
+    // Number the next point pushed gets
+    pub fn pushed(&self) -> usize {
+        self.len
+    }
+
+    // Number of the oldest point kept
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    // Coarsest level whose buckets hold no more than the given number of points, None when single points are finer
+    // than that
+    pub fn level_for(&self, points: f64) -> Option<usize> {
+        let mut level = None;
+        for k in 0..self.levels.len() {
+            if size(k) as f64 > points {
+                break;
+            }
+            level = Some(k);
+        }
+        level
+    }
+
+    fn bucket(&self, k: usize, j: usize) -> Option<&Bucket> {
+        if j < self.front[k] {
+            return None;
+        }
+        self.levels[k].get(j - self.front[k])
+    }
+
+    // Lowest and highest point of every bucket of a level within points a up to b, with finer buckets at the ends, in
+    // the order they were pushed. With no level every point is given, raw looks up the time and value of a point by
+    // its number
+    pub fn cover<F>(
+        &self,
+        level: Option<usize>,
+        a: usize,
+        b: usize,
+        raw: &F,
+        out: &mut Vec<Extreme>,
+    ) where
+        F: Fn(usize) -> (f64, f64),
+    {
+        let a = a.max(self.live);
+        let b = b.min(self.len);
+        if a >= b {
+            return;
+        }
+        let k = match level {
+            Some(k) => k.min(self.levels.len() - 1),
+            None => {
+                for seq in a..b {
+                    let (x, y) = raw(seq);
+                    out.push(Extreme { seq, x, y });
+                }
+                return;
+            }
+        };
+        let size = size(k);
+        let mut j = a / size;
+        while j * size < b {
+            let start = j * size;
+            match self.bucket(k, j) {
+                Some(bk) if start >= a && (start + size).min(self.len) <= b => {
+                    let (first, second) = if bk.lo.seq <= bk.hi.seq {
+                        (bk.lo, bk.hi)
+                    } else {
+                        (bk.hi, bk.lo)
+                    };
+                    out.push(first);
+                    if second.seq != first.seq {
+                        out.push(second);
+                    }
+                }
+                _ => {
+                    let finer = if k == 0 { None } else { Some(k - 1) };
+                    self.cover(finer, start.max(a), (start + size).min(b), raw, out);
+                }
+            }
+            j += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(seq: usize) -> f64 {
+        ((seq * 37) % 101) as f64
+    }
+
+    fn pyramid(n: usize) -> Pyramid {
+        let mut p = Pyramid::new();
+        for seq in 0..n {
+            p.push(seq as f64, value(seq));
+        }
+        p
+    }
+
+    fn raw(seq: usize) -> (f64, f64) {
+        (seq as f64, value(seq))
+    }
+
+    fn cover(p: &Pyramid, level: Option<usize>, a: usize, b: usize) -> Vec<Extreme> {
+        let mut out = Vec::new();
+        p.cover(level, a, b, &raw, &mut out);
+        out
+    }
+
+    // Points given are in order, within a up to b, and include the lowest and highest of them
+    fn check(out: &[Extreme], a: usize, b: usize) {
+        assert!(out.windows(2).all(|w| w[0].seq < w[1].seq));
+        assert!(out.iter().all(|e| e.seq >= a && e.seq < b));
+        assert!(out
+            .iter()
+            .all(|e| e.x == e.seq as f64 && e.y == value(e.seq)));
+        let lo = (a..b).map(value).fold(f64::INFINITY, f64::min);
+        let hi = (a..b).map(value).fold(f64::NEG_INFINITY, f64::max);
+        assert!(out.iter().any(|e| e.y == lo));
+        assert!(out.iter().any(|e| e.y == hi));
+    }
+
+    #[test]
+    fn levels_are_added_as_points_come() {
+        let p = pyramid(FANOUT * FANOUT * FANOUT + 1);
+        assert_eq!(p.level_for(1.), None);
+        assert_eq!(p.level_for(FANOUT as f64), Some(0));
+        assert_eq!(p.level_for((FANOUT * FANOUT) as f64 + 1.), Some(1));
+        assert_eq!(p.level_for(1e9), Some(p.levels.len() - 1));
+        assert_eq!(p.pushed(), FANOUT * FANOUT * FANOUT + 1);
+    }
+
+    #[test]
+    fn no_level_gives_every_point() {
+        let p = pyramid(100);
+        let out = cover(&p, None, 10, 20);
+        assert_eq!(
+            out.iter().map(|e| e.seq).collect::<Vec<_>>(),
+            (10..20).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn buckets_cover_the_middle_and_finer_levels_the_ends() {
+        let p = pyramid(1000);
+        for &(a, b) in &[
+            (0, 1000),
+            (3, 997),
+            (64, 128),
+            (70, 71),
+            (500, 1000),
+            (999, 2000),
+        ] {
+            for level in 0..3 {
+                let out = cover(&p, Some(level), a, b);
+                check(&out, a, b.min(1000));
+                assert!(
+                    out.len() <= 2 * ((b.min(1000) - a) / size(level) + 2 * FANOUT * (level + 1))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn coarse_cover_is_smaller() {
+        let p = pyramid(4096);
+        let fine = cover(&p, Some(0), 0, 4096);
+        let coarse = cover(&p, Some(2), 0, 4096);
+        assert_eq!(fine.len(), 2 * 4096 / FANOUT);
+        assert_eq!(coarse.len(), 2 * 4096 / size(2));
+    }
+
+    #[test]
+    fn popped_points_are_never_covered() {
+        let mut p = pyramid(1000);
+        for _ in 0..77 {
+            p.pop_front();
+        }
+        assert_eq!(p.live(), 77);
+        for level in 0..3 {
+            let out = cover(&p, Some(level), 0, 1000);
+            check(&out, 77, 1000);
+        }
+        assert!(cover(&p, None, 0, 50).is_empty());
+    }
+
+    #[test]
+    fn buckets_go_once_every_point_is_popped() {
+        let mut p = pyramid(FANOUT * FANOUT);
+        assert_eq!(p.bytes(), FANOUT * size_of::<Bucket>());
+        for _ in 0..FANOUT {
+            p.pop_front();
+        }
+        assert_eq!(p.levels[0].len(), FANOUT - 1);
+        assert_eq!(p.front[0], 1);
+        for _ in FANOUT..FANOUT * FANOUT {
+            p.pop_front();
+        }
+        assert!(p.levels.iter().all(|l| l.is_empty()));
+        assert_eq!(p.bytes(), 0);
+        p.push(0., 1.);
+        let out = cover(&p, Some(0), 0, p.pushed());
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].seq, FANOUT * FANOUT);
+    }
+}
@@ -0,0 +1,115 @@
+// Lines that were passed through rather than plotted, kept so they can be shown alongside the data
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub number: usize, // Position of the line in the stream it came from
+    pub time: f64,     // Time the line was received in us
+    pub text: String,
+    pub origin: Option<String>, // Connection the line came from, None for stdin
+}
+
+// Most recent lines, every line pushed gets the next position so places in the log can be held on to while old
+// lines are dropped. Also holds how the log pane shows them
+pub struct Log {
+    lines: VecDeque<LogLine>,
+    first: usize, // Position of the oldest line kept
+    cap: usize,
+    pub visible: bool,
+    pub filter: String, // Only lines containing this are shown, all lines when empty
+    pub scroll: usize, // Shown lines skipped from the newest end, 0 follows new lines as they arrive
+}
+
+impl Log {
+    pub fn new(cap: usize) -> Log {
+        Log {
+            lines: VecDeque::new(),
+            first: 0,
+            cap: cap.max(1),
+            visible: true,
+            filter: String::new(),
+            scroll: 0,
+        }
+    }
+
+    pub fn push(&mut self, line: LogLine) {
+        if self.scroll > 0 && self.passes(&line) {
+            self.scroll += 1; // Keep the pane where it was scrolled to
+        }
+        if self.lines.len() == self.cap {
+            self.lines.pop_front();
+            self.first += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    // Position the next line pushed will get
+    pub fn end(&self) -> usize {
+        self.first + self.lines.len()
+    }
+
+    // Lines still kept with positions from start up to but not including end
+    pub fn range(&self, start: usize, end: usize) -> impl Iterator<Item = (usize, &LogLine)> {
+        let start = start.max(self.first).min(self.end());
+        let end = end.min(self.end()).max(start);
+        self.lines
+            .range(start - self.first..end - self.first)
+            .enumerate()
+            .map(move |(i, l)| (start + i, l))
+    }
+
+    // Lines that pass the filter, newest first, starting from the scroll position
+    pub fn shown(&self) -> impl Iterator<Item = &LogLine> {
+        self.lines
+            .iter()
+            .rev()
+            .filter(move |l| self.passes(l))
+            .skip(self.scroll)
+    }
+
+    fn passes(&self, line: &LogLine) -> bool {
+        self.filter.is_empty() || line.text.contains(self.filter.as_str())
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        let shown = self.lines.iter().filter(|l| self.passes(l)).count();
+        let scroll = (self.scroll as isize + lines).max(0) as usize;
+        self.scroll = scroll.min(shown.saturating_sub(1));
+    }
+}
+
+// Text of the lines points were read from, looked up by line number and receive time since line numbers repeat across
+// connections. Nothing is kept with a capacity of 0
+pub struct SourceLines {
+    lines: HashMap<(usize, u64), LogLine>,
+    order: VecDeque<(usize, u64)>, // Oldest first, to know which line to drop
+    cap: usize,
+}
+
+impl SourceLines {
+    pub fn new(cap: usize) -> SourceLines {
+        SourceLines {
+            lines: HashMap::new(),
+            order: VecDeque::new(),
+            cap,
+        }
+    }
+
+    pub fn push(&mut self, line: LogLine) {
+        if self.cap == 0 {
+            return;
+        }
+        if self.order.len() == self.cap {
+            if let Some(old) = self.order.pop_front() {
+                self.lines.remove(&old);
+            }
+        }
+        let key = (line.number, line.time.to_bits());
+        self.order.push_back(key);
+        self.lines.insert(key, line);
+    }
+
+    pub fn get(&self, number: usize, time: f64) -> Option<&LogLine> {
+        self.lines.get(&(number, time.to_bits()))
+    }
+}
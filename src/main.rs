@@ -1,73 +1,157 @@
 #[macro_use]
 extern crate glium;
-extern crate regex;
 #[macro_use]
 extern crate clap;
 extern crate crossbeam_channel as channel;
 
 extern crate glimput;
+extern crate scope;
 
-use channel::{Receiver, RecvTimeoutError, Sender};
+use channel::RecvTimeoutError;
 use glium::glutin::event_loop::ControlFlow;
 use glium::glutin::window::WindowBuilder;
 use glium::Surface;
 
-use std::f64::NAN;
 use std::thread::sleep;
 use std::time;
 use time::{Duration, Instant};
 // use std::rc::Rc;
 
 use clap::App;
-use regex::{Captures, Regex, RegexSet};
+use std::collections::HashMap;
 use std::io::{self, Write};
 
 pub mod command_parse;
 pub mod drawstyles;
+pub mod export;
+pub mod lod;
+pub mod log;
 pub mod signal;
 pub mod ui;
 
+use log::{LogLine, SourceLines};
+use scope::delimited::CsvSettings;
+use scope::ingest::{duration2us, Interner, ParseError, Parsed, Parser, ReaderSettings};
+use scope::json::JsonSettings;
+use scope::queue::{self, Overload, Receiver, Sender, Stats, Weight};
+use scope::rules::{self, Rule};
+use scope::session::{Recorder, Session, Speed};
+use scope::source::{Data, Line, Protocol, Source};
+use scope::spill;
+use signal::MsgPoint as Point;
 use signal::SignalManager;
-use signal::{MsgPoint as Point, PointType};
 
 use ui::UI;
 
-fn duration2us(dur: &Duration) -> f64 {
-    (dur.as_secs() * 1000_000 + dur.subsec_nanos() as u64 / 1000) as f64
-}
-
 //TODO: Forward std error
 fn main() {
     //Mark the start of the program
     let epoch = Instant::now();
-    //Start a thread to begin polling standard input for new data, any new lines are timestamped and passed along the parsing thread
-    let (send_stdin, rx_stdin): (
-        Sender<(Duration, String, usize)>,
-        Receiver<(Duration, String, usize)>,
-    ) = channel::unbounded();
-    let _read_thread = std::thread::spawn(move || {
-        let mut line_number = 0usize;
-        let mut buffer = String::new();
-        loop {
-            match io::stdin().read_line(&mut buffer) {
-                Ok(0) => break, // EOF Reached
-                Ok(_n) => {
-                    // Received n bytes
-                    match send_stdin.send((epoch.elapsed(), buffer.clone(), line_number)) {
-                        Err(e) => println!("{:?}", e), // Error on channel
-                        _ => {}
-                    }
-                    line_number += 1;
-                }
-                Err(error) => println!("{:?}", error),
-            }
-            buffer.clear();
-        }
-        println!("EOF Reached"); //TODO: Visual signal on key that pipe is closed
-    });
     //Parse comand line arguments
     let cmdargs = load_yaml!("../resources/cmdargs.yml");
-    let _matches = App::from_yaml(cmdargs).get_matches();
+    let matches = App::from_yaml(cmdargs).get_matches();
+    let names = |arg| -> Vec<String> {
+        matches
+            .values_of(arg)
+            .map_or(Vec::new(), |v| v.map(String::from).collect())
+    };
+    let columns = names("columns");
+    let mut json = JsonSettings::default();
+    for pair in names("json-map") {
+        if let Err(e) = json.map(&pair) {
+            println!("{}", e);
+        }
+    }
+    let mut rules = Vec::new();
+    for r in names("rule") {
+        match Rule::new(&r) {
+            Ok(r) => rules.push(r),
+            Err(e) => println!("{}", e),
+        }
+    }
+    if let Some(path) = matches.value_of("rules") {
+        match rules::load(path) {
+            Ok(r) => rules.extend(r),
+            Err(e) => println!("{}", e),
+        }
+    }
+    if matches.is_present("test-rules") {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        if let Err(e) = rules::test(&rules, stdin.lock(), &mut stdout.lock()) {
+            println!("{}", e);
+        }
+        return;
+    }
+    let source_lines = value_t!(matches, "source-lines", usize).unwrap_or(0);
+    let settings = ReaderSettings {
+        capture: names("capture"),
+        pass: names("pass"),
+        csv: CsvSettings {
+            strict: matches.is_present("csv") || columns.len() > 0,
+            delimiter: matches.value_of("delimiter").map(|d| match d {
+                "tab" | "\\t" => '\t',
+                "space" => ' ',
+                _ => d.chars().next().unwrap_or(','),
+            }),
+            columns,
+            x_column: matches.value_of("x-column").map(String::from),
+        },
+        json,
+        tag_origin: matches.is_present("tag"),
+        rules,
+        keep_source: source_lines > 0,
+    };
+
+    let capacity = value_t!(matches, "queue", usize).unwrap_or(100_000);
+    let overload = match matches.value_of("overload").unwrap_or("block").parse() {
+        Ok(o) => o,
+        Err(e) => {
+            println!("{}", e);
+            Overload::Block
+        }
+    };
+    let stats = Stats::default();
+
+    //Start a thread per input source, any new lines are timestamped and passed along the parsing thread
+    let (send_lines, rx_lines): (Sender<Line>, Receiver<Line>) =
+        queue::bounded(capacity, overload, &stats);
+    let mut player = None;
+    let mut sources = Vec::new();
+    match matches.value_of("replay") {
+        Some(path) => match Session::load(path) {
+            Ok(session) => {
+                let speed = matches
+                    .value_of("speed")
+                    .unwrap_or("real")
+                    .parse()
+                    .unwrap_or_else(|e| {
+                        println!("{}", e);
+                        Speed::Real
+                    });
+                println!("Replaying {}, {:.3}s long", path, session.length() / 1e6);
+                player = Some(session.spawn(speed, send_lines.producer("replay")));
+            }
+            Err(e) => println!("{}", e),
+        },
+        None => sources.push(Source::Stdin), // A replay stands in for stdin
+    }
+    sources.extend(names("tcp").into_iter().map(Source::Tcp));
+    sources.extend(names("udp").into_iter().map(Source::Udp));
+    sources.extend(names("unix").into_iter().map(Source::Unix));
+    let binary = names("binary");
+    let _read_threads: Vec<_> = sources
+        .into_iter()
+        .map(|s| {
+            let protocol = if binary.iter().any(|b| b == s.name()) {
+                Protocol::Binary
+            } else {
+                Protocol::Text
+            };
+            let tx = send_lines.producer(s.name());
+            s.spawn(protocol, epoch, tx)
+        })
+        .collect();
 
     //Setup GUI
     use glium::glutin;
@@ -80,15 +164,45 @@ fn main() {
     let display: &'static glium::Display = Box::leak(Box::new(display));
 
     //Spawn point processing thread
-    let settings = ReaderSettings {};
-    let (send_points, rx_points): (Sender<Point>, Receiver<Point>) = channel::unbounded();
+    let (send_points, rx_points): (Sender<Batch>, Receiver<Batch>) =
+        queue::bounded(capacity, overload, &stats);
+    let send_points = send_points.producer("parser");
+    let recorder = matches
+        .value_of("record")
+        .and_then(|path| match Recorder::create(path) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                println!("{}: {}", path, e);
+                None
+            }
+        });
     let _parse_thread = std::thread::spawn(move || {
-        read_thread_main(&rx_stdin, &send_points, &settings);
+        read_thread_main(&rx_lines, &send_points, &settings, recorder);
     });
 
     display.gl_window().window().set_cursor_visible(false);
 
     let mut ui = UI::new(&display);
+    ui.signal_manager.queues = stats;
+    ui.signal_manager.hold = capacity;
+    ui.signal_manager.overload = overload;
+    ui.signal_manager.sources = SourceLines::new(source_lines);
+    ui.signal_manager.player = player;
+    if let Some(h) = matches.value_of("history") {
+        match h.parse() {
+            Ok(h) => ui.signal_manager.history = h,
+            Err(e) => println!("{}", e),
+        }
+    }
+    if let Some(m) = matches.value_of("memory") {
+        match spill::parse_limit(m) {
+            Ok(l) => ui.signal_manager.memory.set_limit(l),
+            Err(e) => println!("{}", e),
+        }
+    }
+    let export = matches
+        .values_of("export")
+        .map(|v| v.collect::<Vec<&str>>().join(" "));
 
     // display.get_free_video_memory()
 
@@ -106,7 +220,13 @@ fn main() {
         for ev in events {
             match ev {
                 Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => action = util::Action::Stop,
+                    WindowEvent::CloseRequested => {
+                        if let Some(ref args) = export {
+                            let cmd = format!("export all {}", args);
+                            command_parse::parse(&cmd, true, &mut ui.signal_manager);
+                        }
+                        action = util::Action::Stop
+                    }
                     WindowEvent::Resized { .. } => {
                         window_size = display.get_framebuffer_dimensions();
                     }
@@ -146,20 +266,51 @@ fn main() {
     });
 }
 
+// What the parser hands the ui, in the order it was read and a read's worth at a time
+enum Msg {
+    Point(Point),
+    Error(ParseError), // Reason a line addressed to a signal could not be read
+    Log(LogLine),      // Line that was passed through
+    Source(LogLine),   // Line that points were read from, only sent when keeping them
+}
+struct Batch {
+    msgs: Vec<Msg>,
+    lines: usize, // Lines of input the messages came from, what the batch counts for in the queue
+}
+
+impl Weight for Batch {
+    fn weight(&self) -> usize {
+        self.lines
+    }
+}
+
 fn get_points(
-    rx: &Receiver<Point>,
+    rx: &Receiver<Batch>,
     man: &mut SignalManager,
     frametime: &Instant,
     refresh_rate: &Duration,
 ) {
     loop {
+        if man.holding_full() {
+            if let Some(rest) = refresh_rate.checked_sub(frametime.elapsed()) {
+                std::thread::sleep(rest);
+            }
+            return;
+        }
         match rx.recv_timeout(
             refresh_rate
                 .checked_sub(frametime.elapsed())
                 .unwrap_or_else(|| Duration::from_millis(0)),
         ) {
-            Ok(d) => {
-                man.add_point(d);
+            Ok(batch) => {
+                for msg in batch.msgs {
+                    match msg {
+                        Msg::Point(d) => man.add_point(d),
+                        Msg::Error(e) => man.report(&e),
+                        Msg::Log(l) => man.log.push(l),
+                        Msg::Source(l) => man.sources.push(l),
+                    }
+                }
             }
             Err(e) => {
                 match e {
@@ -175,157 +326,142 @@ fn get_points(
     }
 }
 
-#[derive(Debug)]
-struct ReaderSettings {}
-
 fn read_thread_main(
-    rx_stdin: &Receiver<(Duration, String, usize)>,
-    send_points: &Sender<Point>,
+    rx_lines: &Receiver<Line>,
+    send_points: &Sender<Batch>,
     settings: &ReaderSettings,
+    mut recorder: Option<Recorder>,
 ) {
-    let deci: &'static str = r"[-+]?[0-9]*\.?[0-9]+(?:[eE][-+]?[0-9]+)?";
-
-    let csv = &format!("^(?:({})(?:\\s+|,\\s*|$)){{1,3}}$", deci);
-    let csvgrab = Regex::new(csv).unwrap();
-
-    let one = &format!("~\\.(.+)@\\s*({})", deci);
-    let two = &format!("~\\.(.+)@\\s*({})\\s*,\\s*({})", deci, deci);
-    let three = &format!(
-        "~\\.(.+)@\\s*({})\\s*,\\s*({})\\s*,\\s*({})",
-        deci, deci, deci
-    );
-    let list = &format!(r"~#(.+)#(?:(\d+),(\d+))?@((?:(?:{})|,|\(|\)|\s)+)", deci); //Untested
-    let node = r"~%(.+)@(\d+)\[((?:\d+|,|\s)*)\]"; //Untested
-    let set = RegexSet::new(&[one, two, three, list, node]).unwrap(); //Guaranteed to unwrap since static input
-    let onegrab = Regex::new(one).unwrap(); //Guaranteed to unwrap since static input
-    let twograb = Regex::new(two).unwrap(); //Guaranteed to unwrap since static input
-    let threegrab = Regex::new(three).unwrap(); //Guaranteed to unwrap since static input
-    let listgrab = Regex::new(list).unwrap();
-    let nodegrab = Regex::new(node).unwrap();
-    let grabbers = [onegrab, twograb, threegrab, listgrab, nodegrab]; // must match order of regex set constructor
+    // Every connection gets its own parser so csv headers and relative times do not leak between them
+    let mut parsers: HashMap<Option<String>, Parser> = HashMap::new();
 
     loop {
-        match rx_stdin.recv() {
-            Ok(d) => parse_line(
-                &d.0,
-                &d.1,
-                d.2,
-                send_points,
-                &set,
-                &grabbers,
-                settings,
-                &csvgrab,
-            ),
+        match rx_lines.recv() {
+            Ok(line) => {
+                if let Some(ref mut r) = recorder {
+                    if let Err(e) = r.write(&line) {
+                        println!("Recording stopped: {}", e);
+                        recorder = None;
+                    }
+                }
+                let parser = parsers
+                    .entry(line.origin.clone())
+                    .or_insert_with(|| Parser::new(settings.csv.clone(), settings.json.clone()));
+                parse_line(parser, line, send_points, settings)
+            }
             Err(_) => sleep(Duration::from_secs(1)),
         }
     }
 }
 
-fn parse_line(
-    timestamp: &Duration,
-    in_data: &String,
-    ln: usize,
-    tx: &Sender<Point>,
-    set: &RegexSet,
-    grabbers: &[Regex],
-    settings: &ReaderSettings,
-    iscsv: &Regex,
-) {
-    let ts = duration2us(timestamp);
-
-    //Quick and dirty hack to accept CSVs
-    let mut data = in_data;
-    let mut s;
-    if iscsv.is_match(data) {
-        s = String::from("~.csv@");
-        s.push_str(data);
-        data = &s
-    }
-
-    let which: Vec<usize> = set.matches(data).into_iter().collect();
-    if which.len() == 0 {
-        //No candidate matches
-        passthrough(data);
-    } else {
-        let idx = which.into_iter().fold(0, std::cmp::max); // Get the most desired candidate that will match
-        match handle_caps(grabbers[idx].captures(data), ts, ln, settings) {
-            // Match the result of handling the capture groups. If a valid point was found send it. Otherwise pass line through and log it.
-            Some(tosend) => {
-                //Vaild point send to main thread
-                match tx.send(tosend) {
-                    Ok(_) => {}
-                    Err(e) => println!("{:?}", e), // Error on channel
+// Text arrives as many lines read at once, all points parsed from them go to the ui together
+fn parse_line(parser: &mut Parser, line: Line, tx: &Sender<Batch>, settings: &ReaderSettings) {
+    let tag = match line.origin {
+        Some(ref o) if settings.tag_origin => Some(o.as_str()),
+        _ => None,
+    };
+    let ts = duration2us(&line.time);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let lines = line.weight();
+    let mut batch = Vec::new();
+    match line.data {
+        Data::Text(ref chunk) => {
+            for (i, text) in chunk.split_inclusive('\n').enumerate() {
+                let n = line.number + i;
+                let parsed = match parser.parse(text, &line.time, n) {
+                    Ok(Parsed::Passthrough) => {
+                        rules::read(&settings.rules, parser.names(), text, ts, n)
+                            .unwrap_or(Ok(Parsed::Passthrough))
+                    }
+                    parsed => parsed,
+                };
+                let start = batch.len();
+                let pass = collect(parsed, tag, parser.names(), settings, &mut batch);
+                let plotted = batch[start..].iter().any(|m| match m {
+                    Msg::Point(_) => true,
+                    _ => false,
+                });
+                if !pass && !(plotted && settings.keep_source) {
+                    continue;
+                }
+                let log = LogLine {
+                    number: n,
+                    time: ts,
+                    text: String::from(text.trim_end_matches(|c| c == '\n' || c == '\r')),
+                    origin: line.origin.clone(),
+                };
+                if plotted && settings.keep_source {
+                    batch.push(Msg::Source(log.clone()));
                 }
+                if pass {
+                    passthrough(&mut out, text);
+                    batch.push(Msg::Log(log));
+                }
+            }
+        }
+        // Binary input is never passed through, stdout is text
+        Data::Samples(samples) => {
+            for sample in samples {
+                collect(
+                    sample.map(Parsed::Point),
+                    tag,
+                    parser.names(),
+                    settings,
+                    &mut batch,
+                );
             }
-            None => passthrough(data), // Data was not valid syntax || marked to be passed through, must be passed on to stdout
         }
     }
+    if batch.is_empty() {
+        return;
+    }
+    if tx.send(Batch { msgs: batch, lines }).is_err() {
+        println!("Points lost, nothing is plotting") // Error on channel
+    }
 }
 
-fn handle_caps(
-    caps: Option<Captures>,
-    timestamp: f64,
-    ln: usize,
+// Adds what was parsed from a line to the batch, true if the line should be passed on
+fn collect(
+    parsed: Result<Parsed, ParseError>,
+    tag: Option<&str>,
+    names: &mut Interner,
     settings: &ReaderSettings,
-) -> Option<Point> {
-    let vals = caps.unwrap(); //Guaranteed unwrap since captured by RegexSet
-
-    let mut c = vals.iter();
-    c.next(); // Dont want entire match
-    let name = String::from(c.next().unwrap().unwrap().as_str()); //TODO: Guarantee unwraps
-
-    //TODO: Filter out signals
-
-    //convert remaining values to floats
-    let v: Vec<f64> = c
-        .map(|m| {
-            m.map_or(0.0, |m| m.as_str().parse::<f64>().unwrap()) //TODO: should fail gracefully here, set channel health to bad
-        })
-        .collect();
-
-    return Some(match v.len() {
-        1 => Point {
-            name,
-            line_number: ln,
-            timestamp,
-            ty: PointType::D1,
-            x: v[0],
-            y: NAN,
-            z: NAN,
-        },
-        2 => Point {
-            name,
-            line_number: ln,
-            timestamp,
-            ty: PointType::D2,
-            x: v[0],
-            y: v[1],
-            z: NAN,
-        },
-        3 => Point {
-            name,
-            line_number: ln,
-            timestamp,
-            ty: PointType::D3,
-            x: v[0],
-            y: v[1],
-            z: v[2],
-        },
-        _ => Point {
-            name,
-            line_number: ln,
-            timestamp,
-            ty: PointType::BreakPoint,
-            x: NAN,
-            y: NAN,
-            z: NAN,
-        },
-    });
+    batch: &mut Vec<Msg>,
+) -> bool {
+    let points = match parsed {
+        Ok(Parsed::Point(p)) => vec![p],
+        Ok(Parsed::Points(p)) => p,
+        Ok(Parsed::Passthrough) => return true, // Not meant for us, must be passed on to stdout
+        Err(mut e) => {
+            if let Some(o) = tag {
+                e.tag(o);
+            }
+            if e.signal_names().iter().any(|n| settings.captures(n)) {
+                batch.push(Msg::Error(e));
+            }
+            return true;
+        }
+    };
+    let mut pass = false;
+    for mut p in points {
+        if let Some(o) = tag {
+            p.name = names.tagged(o, &p.name);
+        }
+        if !settings.captures(&p.name) {
+            pass = true;
+            continue;
+        }
+        pass |= settings.passes(&p.name);
+        //Vaild point send to main thread
+        batch.push(Msg::Point(p));
+    }
+    pass
 }
 
-fn passthrough(line: &String) {
-    // Log data for later examination if breakpoint is reached etc
-    if let Ok(_) = write!(io::stdout(), "{}", line) {}
+fn passthrough(out: &mut Write, line: &str) {
+    // Also kept in the signal manager's log to be shown around breakpoints
+    if let Ok(_) = write!(out, "{}", line) {}
 }
 
 // stolen from here cause glutin event loop is dumb: https://docs.rs/crate/glium/0.31.0/source/examples/support/mod.rs
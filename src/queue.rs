@@ -0,0 +1,376 @@
+// Bounded channels between the input sources, the parser and the ui. Queues are bounded by the weight of what waits in
+// them, lines of input rather than the reads or batches carrying them. What happens when a queue is full is chosen by an
+// Overload policy, and every producer keeps counters of what it queued and lost so overload can be seen and queried
+use channel::{self, RecvError, RecvTimeoutError, TryRecvError};
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overload {
+    Block,      // Producer waits for room, stdin backs up into whatever is writing to it
+    DropNewest, // Item being sent is thrown away
+    DropOldest, // Oldest item in the queue is thrown away to make room
+    Decimate, // Every other item is thrown away while the queue is over half full, then drop newest once full
+}
+
+impl FromStr for Overload {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Overload, String> {
+        match s {
+            "block" => Ok(Overload::Block),
+            "drop-newest" => Ok(Overload::DropNewest),
+            "drop-oldest" => Ok(Overload::DropOldest),
+            "decimate" => Ok(Overload::Decimate),
+            _ => Err(format!("Unknown overload policy {:?}", s)),
+        }
+    }
+}
+
+// Lines, or whatever else items weigh, of one producer
+#[derive(Debug, Default)]
+pub struct Counters {
+    pub offered: AtomicUsize, // Everything the producer tried to send
+    pub pending: AtomicUsize, // Sent but not yet received
+    pub dropped: AtomicUsize,
+    items: AtomicUsize, // Items offered, decimation drops every other one
+}
+
+impl Counters {
+    fn add(c: &AtomicUsize, n: usize) {
+        c.fetch_add(n, Ordering::Relaxed);
+    }
+    fn sub(c: &AtomicUsize, n: usize) {
+        c.fetch_sub(n, Ordering::Relaxed);
+    }
+    pub fn get(c: &AtomicUsize) -> usize {
+        c.load(Ordering::Relaxed)
+    }
+}
+
+type Producers = Vec<(String, Arc<Counters>)>;
+
+// Counters of every producer by name, shared with whatever displays them
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    producers: Arc<Mutex<Producers>>,
+}
+
+impl Stats {
+    fn register(&self, name: &str) -> Arc<Counters> {
+        let c = Arc::new(Counters::default());
+        if let Ok(mut p) = self.producers.lock() {
+            p.push((String::from(name), c.clone()));
+        }
+        c
+    }
+    // Name, lines pending and lines dropped of every producer
+    pub fn snapshot(&self) -> Vec<(String, usize, usize)> {
+        match self.producers.lock() {
+            Ok(p) => p
+                .iter()
+                .map(|(n, c)| {
+                    (
+                        n.clone(),
+                        Counters::get(&c.pending),
+                        Counters::get(&c.dropped),
+                    )
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Every item travels with its weight and the counters of the producer that sent it so drops and receipts are charged to
+// it
+type Tagged<T> = (T, usize, Arc<Counters>);
+
+// What an item counts for against the capacity of a queue, the lines it holds for input
+pub trait Weight {
+    fn weight(&self) -> usize;
+}
+
+// Weight waiting in a queue, shared by its senders and receiver. Blocked senders wait on room
+#[derive(Debug, Default)]
+struct Level {
+    cap: usize,
+    queued: Mutex<usize>,
+    room: Condvar,
+    closed: AtomicBool, // Receiver is gone, nobody will make room
+}
+
+impl Level {
+    fn lock(&self) -> MutexGuard<'_, usize> {
+        self.queued.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn take(&self, weight: usize) {
+        let mut q = self.lock();
+        *q = q.saturating_sub(weight);
+        self.room.notify_all();
+    }
+    // An item always fits an empty queue, however heavy
+    fn fits(&self, queued: usize, weight: usize) -> bool {
+        queued == 0 || queued + weight <= self.cap
+    }
+}
+
+// What sending gives once the receiver is gone
+#[derive(Debug, PartialEq)]
+pub struct Closed;
+
+pub struct Sender<T> {
+    tx: channel::Sender<Tagged<T>>,
+    rx: Option<channel::Receiver<Tagged<T>>>, // Lets drop oldest take items back out
+    level: Arc<Level>,
+    policy: Overload,
+    counters: Arc<Counters>,
+    stats: Stats,
+}
+
+pub struct Receiver<T> {
+    rx: channel::Receiver<Tagged<T>>,
+    level: Arc<Level>,
+}
+
+// Holds up to cap weight of items. Counters of the sender returned are not registered, take a producer from it for
+// every source that should be listed
+pub fn bounded<T>(cap: usize, policy: Overload, stats: &Stats) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = channel::unbounded();
+    let level = Arc::new(Level {
+        cap: cap.max(1),
+        ..Level::default()
+    });
+    (
+        Sender {
+            tx,
+            rx: if policy == Overload::DropOldest {
+                Some(rx.clone())
+            } else {
+                None
+            },
+            level: level.clone(),
+            policy,
+            counters: Arc::new(Counters::default()),
+            stats: stats.clone(),
+        },
+        Receiver { rx, level },
+    )
+}
+
+impl<T: Weight> Sender<T> {
+    // Sender into the same queue with counters of its own
+    pub fn producer(&self, name: &str) -> Sender<T> {
+        Sender {
+            tx: self.tx.clone(),
+            rx: self.rx.clone(),
+            level: self.level.clone(),
+            policy: self.policy,
+            counters: self.stats.register(name),
+            stats: self.stats.clone(),
+        }
+    }
+
+    // Err once nothing is receiving any more
+    pub fn send(&self, item: T) -> Result<(), Closed> {
+        let c = &self.counters;
+        let weight = item.weight();
+        Counters::add(&c.offered, weight);
+        Counters::add(&c.items, 1);
+        // Drop oldest senders hold a receiver of their own, the channel alone never tells them the real one is gone
+        if self.level.closed.load(Ordering::Relaxed) {
+            return Err(Closed);
+        }
+        let mut queued = self.level.lock();
+        if self.policy == Overload::Decimate
+            && *queued > self.level.cap / 2
+            && Counters::get(&c.items).is_multiple_of(2)
+        {
+            Counters::add(&c.dropped, weight);
+            return Ok(());
+        }
+        while !self.level.fits(*queued, weight) {
+            if self.level.closed.load(Ordering::Relaxed) {
+                return Err(Closed);
+            }
+            match (self.policy, &self.rx) {
+                (Overload::Block, _) => {
+                    queued = self
+                        .level
+                        .room
+                        .wait(queued)
+                        .unwrap_or_else(|e| e.into_inner())
+                }
+                (Overload::DropOldest, Some(rx)) => match rx.try_recv() {
+                    Ok(old) => {
+                        *queued = queued.saturating_sub(old.1);
+                        Self::discard(old);
+                    }
+                    Err(_) => break, // Taken by the receiver, which is about to make room
+                },
+                _ => {
+                    Counters::add(&c.dropped, weight);
+                    return Ok(());
+                }
+            }
+        }
+        *queued += weight;
+        drop(queued);
+        Counters::add(&c.pending, weight);
+        self.tx.send((item, weight, c.clone())).map_err(|e| {
+            let item = e.into_inner();
+            self.level.take(item.1);
+            Self::lost(item)
+        })
+    }
+
+    fn discard(item: Tagged<T>) {
+        Counters::sub(&item.2.pending, item.1);
+        Counters::add(&item.2.dropped, item.1);
+    }
+    fn lost(item: Tagged<T>) -> Closed {
+        Counters::sub(&item.2.pending, item.1);
+        Closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        Sender {
+            tx: self.tx.clone(),
+            rx: self.rx.clone(),
+            level: self.level.clone(),
+            policy: self.policy,
+            counters: self.counters.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.rx.recv().map(|i| self.received(i))
+    }
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.rx.recv_timeout(timeout).map(|i| self.received(i))
+    }
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.rx.try_recv().map(|i| self.received(i))
+    }
+    fn received(&self, item: Tagged<T>) -> T {
+        self.level.take(item.1);
+        Counters::sub(&item.2.pending, item.1);
+        item.0
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.level.closed.store(true, Ordering::Relaxed);
+        let _q = self.level.lock();
+        self.level.room.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    struct Lines(usize);
+
+    impl Weight for Lines {
+        fn weight(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn drain(rx: &Receiver<Lines>) -> Vec<usize> {
+        let mut got = Vec::new();
+        while let Ok(l) = rx.try_recv() {
+            got.push(l.0);
+        }
+        got
+    }
+
+    #[test]
+    fn bounded_by_weight() {
+        let stats = Stats::default();
+        let (tx, rx) = bounded(10, Overload::DropNewest, &stats);
+        let tx = tx.producer("a");
+        for w in &[4, 4, 4, 2] {
+            tx.send(Lines(*w)).unwrap();
+        }
+        tx.send(Lines(3)).unwrap();
+        assert_eq!(stats.snapshot(), vec![(String::from("a"), 10, 7)]);
+        assert_eq!(drain(&rx), vec![4, 4, 2]);
+        assert_eq!(stats.snapshot(), vec![(String::from("a"), 0, 7)]);
+    }
+
+    #[test]
+    fn heavy_item_fits_empty_queue() {
+        let (tx, rx) = bounded(10, Overload::DropNewest, &Stats::default());
+        tx.send(Lines(50)).unwrap();
+        tx.send(Lines(1)).unwrap();
+        assert_eq!(drain(&rx), vec![50]);
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let (tx, rx) = bounded(10, Overload::DropOldest, &Stats::default());
+        let tx = tx.producer("a");
+        for w in &[3, 3, 3, 5] {
+            tx.send(Lines(*w)).unwrap();
+        }
+        assert_eq!(drain(&rx), vec![3, 5]);
+        assert_eq!(Counters::get(&tx.counters.dropped), 6);
+        assert_eq!(Counters::get(&tx.counters.offered), 14);
+    }
+
+    #[test]
+    fn decimate() {
+        let (tx, rx) = bounded(8, Overload::Decimate, &Stats::default());
+        for _ in 0..8 {
+            tx.send(Lines(1)).unwrap();
+        }
+        // Every other one is dropped past half full, then the queue fills up
+        assert_eq!(drain(&rx).len(), 6);
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let (tx, rx) = bounded(2, Overload::Block, &Stats::default());
+        tx.send(Lines(2)).unwrap();
+        let t = thread::spawn(move || tx.send(Lines(1)));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rx.try_recv().map(|l| l.0), Ok(2));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).map(|l| l.0), Ok(1));
+        assert_eq!(t.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn block_gives_up_without_receiver() {
+        let (tx, rx) = bounded(1, Overload::Block, &Stats::default());
+        tx.send(Lines(1)).unwrap();
+        let t = thread::spawn(move || tx.send(Lines(1)));
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+        assert_eq!(t.join().unwrap(), Err(Closed));
+    }
+
+    #[test]
+    fn drop_oldest_gives_up_without_receiver() {
+        let (tx, rx) = bounded(10, Overload::DropOldest, &Stats::default());
+        tx.send(Lines(1)).unwrap();
+        drop(rx);
+        assert_eq!(tx.send(Lines(1)), Err(Closed));
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!("drop-oldest".parse(), Ok(Overload::DropOldest));
+        assert!("drop".parse::<Overload>().is_err());
+    }
+}
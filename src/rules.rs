@@ -0,0 +1,275 @@
+// Extraction rules for logs that are not written as scope messages. A rule is a regex whose named groups pick a signal
+// out of a line: name gives the signal name, x, y and z its values (at least x) and t the producer time in seconds.
+// Rules are written as [signal=]regex, the signal name is used on its own when the regex has no name group and as a
+// prefix, signal.name, when it does, e.g.
+//
+//   temp=T: (?P<x>[-\d.]+)C
+//   (?P<name>\w+)=(?P<x>[-\d.]+)
+//
+// A regex may match a line several times, every match is its own point. Rules are tried on lines the parser found
+// nothing in and every rule that matches a line adds its points.
+use ingest::{Interner, MsgPoint, ParseError, Parsed, PointType};
+use regex::Regex;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    signal: Option<String>,
+    regex: Regex,
+}
+
+impl Rule {
+    pub fn new(rule: &str) -> Result<Rule, String> {
+        // Only an identifier before the first = is a signal name, anything else is part of the regex
+        let (signal, pattern) = match rule.find('=') {
+            Some(i) if is_name(&rule[..i]) => (Some(String::from(&rule[..i])), &rule[i + 1..]),
+            _ => (None, rule),
+        };
+        let regex = Regex::new(pattern).map_err(|e| format!("Rule {:?}: {}", rule, e))?;
+        let groups: Vec<&str> = regex.capture_names().flatten().collect();
+        if !groups.contains(&"x") {
+            return Err(format!("Rule {:?} has no (?P<x>...) group", rule));
+        }
+        if signal.is_none() && !groups.contains(&"name") {
+            return Err(format!(
+                "Rule {:?} needs a signal= or a (?P<name>...) group",
+                rule
+            ));
+        }
+        Ok(Rule { signal, regex })
+    }
+
+    // Points of every match in the line
+    fn points(
+        &self,
+        names: &mut Interner,
+        line: &str,
+        timestamp: f64,
+        ln: usize,
+    ) -> Result<Vec<MsgPoint>, ParseError> {
+        let mut points = Vec::new();
+        for caps in self.regex.captures_iter(line) {
+            let name = match (&self.signal, caps.name("name")) {
+                (Some(s), Some(n)) => names.get(&format!("{}.{}", s, n.as_str())),
+                (Some(s), None) => names.get(s),
+                (&None, Some(n)) => names.get(n.as_str()),
+                (&None, None) => continue,
+            };
+            let number = |group: &str| match caps.name(group) {
+                Some(m) => m.as_str().trim().parse::<f64>().map(Some).map_err(|_| {
+                    ParseError::InvalidNumber {
+                        name: String::from(&*name),
+                        value: String::from(m.as_str()),
+                    }
+                }),
+                None => Ok(None),
+            };
+            let mut v = [f64::NAN; 3];
+            let mut dims = 0;
+            for group in ["x", "y", "z"].iter() {
+                match number(group)? {
+                    Some(n) => v[dims] = n,
+                    None => break,
+                }
+                dims += 1;
+            }
+            if dims == 0 {
+                continue; // x is optional in the regex and did not take part in this match
+            }
+            let device_time = number("t")?.map_or(f64::NAN, |t| t * 1e6);
+            points.push(MsgPoint {
+                name,
+                line_number: ln,
+                timestamp,
+                device_time,
+                ty: match dims {
+                    1 => PointType::D1,
+                    2 => PointType::D2,
+                    _ => PointType::D3,
+                },
+                x: v[0],
+                y: v[1],
+                z: v[2],
+                values: Vec::new(),
+                shape: (0, 0),
+            });
+        }
+        Ok(points)
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+// Reads a file of rules, one per line. Blank lines and lines starting with # are skipped
+pub fn load(path: &str) -> Result<Vec<Rule>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut rules = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("{}: {}", path, e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        rules.push(Rule::new(line).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?);
+    }
+    Ok(rules)
+}
+
+// None if no rule matched the line
+pub fn read(
+    rules: &[Rule],
+    names: &mut Interner,
+    line: &str,
+    timestamp: f64,
+    ln: usize,
+) -> Option<Result<Parsed, ParseError>> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let mut points = Vec::new();
+    let mut matched = false;
+    for rule in rules {
+        match rule.points(names, line, timestamp, ln) {
+            Ok(p) => {
+                matched |= !p.is_empty();
+                points.extend(p);
+            }
+            Err(e) => return Some(Err(e)),
+        }
+    }
+    if matched {
+        Some(Ok(Parsed::Points(points)))
+    } else {
+        None
+    }
+}
+
+// Shows what every rule takes from each line of input, for trying rules out on a sample of a log
+pub fn test<R: BufRead, W: Write>(rules: &[Rule], input: R, out: &mut W) -> io::Result<()> {
+    let mut names = Interner::default();
+    for (ln, line) in input.lines().enumerate() {
+        let line = line?;
+        writeln!(out, "{}: {}", ln, line)?;
+        let mut matched = false;
+        for (i, rule) in rules.iter().enumerate() {
+            match rule.points(&mut names, &line, f64::NAN, ln) {
+                Ok(ref p) if p.is_empty() => continue,
+                Ok(p) => {
+                    for p in p {
+                        write!(out, "    rule {}: {} x={}", i, p.name, p.x)?;
+                        if !p.y.is_nan() {
+                            write!(out, " y={}", p.y)?;
+                        }
+                        if !p.z.is_nan() {
+                            write!(out, " z={}", p.z)?;
+                        }
+                        if !p.device_time.is_nan() {
+                            write!(out, " t={}s", p.device_time / 1e6)?;
+                        }
+                        writeln!(out)?;
+                    }
+                }
+                Err(e) => writeln!(out, "    rule {}: {}", i, e)?,
+            }
+            matched = true;
+        }
+        if !matched {
+            writeln!(out, "    no rule matched")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(rules: &[&str], line: &str) -> Option<Vec<(String, [f64; 3], f64)>> {
+        let rules: Vec<Rule> = rules.iter().map(|r| Rule::new(r).unwrap()).collect();
+        match read(&rules, &mut Interner::default(), line, 0., 0)? {
+            Ok(Parsed::Points(p)) => Some(
+                p.iter()
+                    .map(|p| (String::from(&*p.name), [p.x, p.y, p.z], p.device_time))
+                    .collect(),
+            ),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(Rule::new("temp=T: ([-\\d.]+)").is_err()); // No x
+        assert!(Rule::new("(?P<x>\\d+)").is_err()); // No name
+        assert!(Rule::new("a=(?P<x>").is_err());
+    }
+
+    #[test]
+    fn signal_name() {
+        let p = points(&["temp=T: (?P<x>[-\\d.]+)C"], "T: -4.5C\n").unwrap();
+        assert_eq!(p[0].0, "temp");
+        assert_eq!(p[0].1[0], -4.5);
+        assert!(p[0].2.is_nan());
+    }
+
+    #[test]
+    fn equals_inside_regex() {
+        // Not an identifier before the =, so it is all regex
+        let p = points(&["(?P<name>\\w+)=(?P<x>\\d+)"], "a=1 b=2").unwrap();
+        let names: Vec<&str> = p.iter().map(|p| p.0.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn prefix_and_dimensions() {
+        let rule = "m=(?P<name>\\w+) (?P<x>\\d+)(,(?P<y>\\d+))?(,(?P<z>\\d+))? at (?P<t>[\\d.]+)";
+        let p = points(&[rule], "rpm 1,2 at 0.5").unwrap();
+        assert_eq!(p[0].0, "m.rpm");
+        assert_eq!((p[0].1[0], p[0].1[1], p[0].2), (1., 2., 5e5));
+        assert!(p[0].1[2].is_nan());
+    }
+
+    #[test]
+    fn every_rule_adds_points() {
+        let p = points(&["a=x(?P<x>\\d)", "b=(?P<x>\\d)y"], "x1 2y").unwrap();
+        assert_eq!(p.len(), 2);
+        assert_eq!(points(&["a=x(?P<x>\\d)"], "nothing"), None);
+    }
+
+    #[test]
+    fn bad_number() {
+        let rules = vec![Rule::new("a=v(?P<x>\\S+)").unwrap()];
+        match read(&rules, &mut Interner::default(), "v1.2.3", 0., 0) {
+            Some(Err(ParseError::InvalidNumber { name, value })) => {
+                assert_eq!((name.as_str(), value.as_str()), ("a", "1.2.3"))
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn tried_out() {
+        let rules = vec![Rule::new("a=v(?P<x>\\d+)").unwrap()];
+        let mut out = Vec::new();
+        test(&rules, "v1\nnone\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "0: v1\n    rule 0: a x=1\n1: none\n    no rule matched\n"
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_name("temp.c_1"));
+        assert!(!is_name("1a"));
+        assert!(!is_name("(?P<x>"));
+        assert!(!is_name(""));
+    }
+}
@@ -0,0 +1,605 @@
+// Hand written scanner for the message grammar. Everything it returns borrows from the line, nothing is allocated
+// until ingest builds points out of it.
+//
+//   ~.name@x[,y[,z]]                up to three values
+//   ~.name@key=x,key=y,...          keyed values, only pairs may follow the @
+//   ~#name#[rows,cols]@(...)        list, numbers grouped by parentheses
+//   ~%name@id[child,...]            node of a graph
+//   ~!name                          breakpoint, the name ends at whitespace, a comma or an @
+//
+// Any message may end in t=[+]<number>[ns|us|ms|s]. Names run up to the last @ (or # for lists) that leaves a valid
+// message and when several forms fit a line the one furthest down this list wins. A ~.name@ that fits neither value
+// form is returned as malformed rather than taken for ordinary text, so is one with more than three values. t is the
+// time and never a key, ~.x@t=5 is malformed.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Time<'a> {
+    pub relative: bool,
+    pub value: &'a str,
+    pub unit: &'a str, // Empty when left out
+}
+
+#[derive(Debug)]
+pub enum Message<'a> {
+    Values {
+        name: &'a str,
+        values: [&'a str; 3],
+        count: usize, // Values given, only the first three are kept
+    },
+    Keyed {
+        name: &'a str,
+        pairs: &'a str, // Walk with Pairs
+    },
+    List {
+        name: &'a str,
+        shape: Option<(&'a str, &'a str)>,
+        payload: &'a str,
+    },
+    Node {
+        name: &'a str,
+        id: &'a str,
+        children: &'a str,
+    },
+    Break {
+        name: &'a str,
+    },
+    Malformed {
+        name: &'a str,
+        payload: &'a str, // Rest of the line after the @
+    },
+}
+
+impl<'a> Message<'a> {
+    pub fn name(&self) -> &'a str {
+        match *self {
+            Message::Values { name, .. }
+            | Message::Keyed { name, .. }
+            | Message::List { name, .. }
+            | Message::Node { name, .. }
+            | Message::Break { name }
+            | Message::Malformed { name, .. } => name,
+        }
+    }
+}
+
+// The message a line carries if it carries one
+pub fn message<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    if !line.contains('~') {
+        return None;
+    }
+    brk(line)
+        .or_else(|| keyed(line))
+        .or_else(|| node(line))
+        .or_else(|| list(line))
+        .or_else(|| values(line))
+        .or_else(|| malformed(line))
+}
+
+#[derive(Clone, Copy)]
+struct Cursor<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || (0x09..=0x0d).contains(&c)
+}
+
+fn is_digit(c: u8) -> bool {
+    c.is_ascii_digit()
+}
+
+impl<'a> Cursor<'a> {
+    fn new(s: &'a str, pos: usize) -> Cursor<'a> {
+        Cursor { s, pos }
+    }
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).cloned()
+    }
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn eat_str(&mut self, s: &str) -> bool {
+        if self.s[self.pos..].starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn skip_while<F: Fn(u8) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        &self.s[start..self.pos]
+    }
+    fn skip_space(&mut self) {
+        self.skip_while(is_space);
+    }
+    fn only_space_left(&self) -> bool {
+        self.s.as_bytes()[self.pos..].iter().all(|c| is_space(*c))
+    }
+
+    // [-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?
+    fn number(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') || self.peek() == Some(b'+') {
+            self.pos += 1;
+        }
+        let int = self.skip_while(is_digit).len();
+        let mut frac = 0;
+        if self.peek() == Some(b'.') {
+            let dot = self.pos;
+            self.pos += 1;
+            frac = self.skip_while(is_digit).len();
+            if frac == 0 {
+                self.pos = dot;
+            }
+        }
+        if int == 0 && frac == 0 {
+            self.pos = start;
+            return None;
+        }
+        if self.peek() == Some(b'e') || self.peek() == Some(b'E') {
+            let e = self.pos;
+            self.pos += 1;
+            if self.peek() == Some(b'-') || self.peek() == Some(b'+') {
+                self.pos += 1;
+            }
+            if self.skip_while(is_digit).is_empty() {
+                self.pos = e;
+            }
+        }
+        Some(&self.s[start..self.pos])
+    }
+
+    // [,\s]*t=[+]<number>[unit], left where it was if there is none
+    fn time(&mut self) -> Option<Time<'a>> {
+        let save = *self;
+        self.skip_while(|c| c == b',' || is_space(c));
+        if self.eat_str("t=") {
+            let relative = self.eat(b'+');
+            if let Some(value) = self.number() {
+                let unit = ["ns", "us", "ms", "s"]
+                    .iter()
+                    .find(|u| self.s[self.pos..].starts_with(*u))
+                    .map_or("", |u| *u);
+                self.pos += unit.len();
+                return Some(Time {
+                    relative,
+                    value,
+                    unit,
+                });
+            }
+        }
+        *self = save;
+        None
+    }
+
+    // [A-Za-z_][\w.]*\s*=\s*<number>
+    fn pair(&mut self) -> Option<(&'a str, &'a str)> {
+        let save = *self;
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == b'_' => {}
+            _ => return None,
+        }
+        let key = self.skip_while(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'.');
+        if key == "t" {
+            *self = save; // The time, not a value
+            return None;
+        }
+        self.skip_space();
+        if self.eat(b'=') {
+            self.skip_space();
+            if let Some(v) = self.number() {
+                return Some((key, v));
+            }
+        }
+        *self = save;
+        None
+    }
+}
+
+// Candidate splits of a message into name and payload, furthest separator first
+fn splits<'a>(line: &'a str, prefix: &str, sep: char) -> Vec<(&'a str, Cursor<'a>)> {
+    let start = match line.find(prefix) {
+        Some(s) => s + prefix.len(),
+        None => return Vec::new(),
+    };
+    let rest = &line[start..];
+    let end = rest.find('\n').unwrap_or(rest.len());
+    rest[..end]
+        .rmatch_indices(sep)
+        .filter(|&(i, _)| i > 0)
+        .map(|(i, _)| (&rest[..i], Cursor::new(line, start + i + 1)))
+        .collect()
+}
+
+fn keyed<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    for (name, mut c) in splits(line, "~.", '@') {
+        c.skip_space();
+        let start = c.pos;
+        let mut end = start;
+        let mut time = None;
+        let mut ok = false;
+        while c.pair().is_some() {
+            end = c.pos;
+            c.skip_space();
+            c.eat(b',');
+            c.skip_space();
+            let mut t = c;
+            if let Some(tm) = t.time() {
+                if t.only_space_left() {
+                    time = Some(tm);
+                    ok = true;
+                    break;
+                }
+            }
+            if c.only_space_left() {
+                ok = true;
+                break;
+            }
+        }
+        if ok {
+            let pairs = &line[start..end];
+            return Some((Message::Keyed { name, pairs }, time));
+        }
+    }
+    None
+}
+
+fn brk<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    let start = line.find("~!")? + 2;
+    let mut c = Cursor::new(line, start);
+    let name = c.skip_while(|c| !is_space(c) && c != b',' && c != b'@');
+    if name.is_empty() {
+        return None;
+    }
+    c.eat(b'@');
+    let time = c.time();
+    Some((Message::Break { name }, time))
+}
+
+fn node<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    for (name, mut c) in splits(line, "~%", '@') {
+        let id = c.skip_while(is_digit);
+        if id.is_empty() || !c.eat(b'[') {
+            continue;
+        }
+        let children = c.skip_while(|c| is_digit(c) || c == b',' || is_space(c));
+        if !c.eat(b']') {
+            continue;
+        }
+        let time = c.time();
+        return Some((Message::Node { name, id, children }, time));
+    }
+    None
+}
+
+fn list<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    for (name, mut c) in splits(line, "~#", '#') {
+        let mut shape = None;
+        let save = c;
+        let rows = c.skip_while(is_digit);
+        if !rows.is_empty() && c.eat(b',') {
+            let cols = c.skip_while(is_digit);
+            if !cols.is_empty() && c.peek() == Some(b'@') {
+                shape = Some((rows, cols));
+            }
+        }
+        if shape.is_none() {
+            c = save;
+        }
+        if !c.eat(b'@') {
+            continue;
+        }
+        let start = c.pos;
+        loop {
+            if c.number().is_none() && !c.eat(b',') && !c.eat(b'(') && !c.eat(b')') {
+                if c.peek().is_some_and(is_space) {
+                    c.pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        if c.pos == start {
+            continue;
+        }
+        let payload = &line[start..c.pos];
+        let time = c.time();
+        return Some((
+            Message::List {
+                name,
+                shape,
+                payload,
+            },
+            time,
+        ));
+    }
+    None
+}
+
+fn values<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    let mut best: Option<(Message<'a>, Option<Time<'a>>)> = None;
+    for (name, mut c) in splits(line, "~.", '@') {
+        c.skip_space();
+        let mut values = [""; 3];
+        let mut count = 0; // Values past the third are counted but not kept
+        loop {
+            let save = c;
+            if count > 0 {
+                c.skip_space();
+                if !c.eat(b',') {
+                    c = save;
+                    break;
+                }
+                c.skip_space();
+            }
+            match c.number() {
+                Some(v) if count < 3 => values[count] = v,
+                Some(_) => {}
+                None => {
+                    c = save;
+                    break;
+                }
+            }
+            count += 1;
+        }
+        let better = match best {
+            Some((Message::Values { count: b, .. }, _)) => count > b,
+            _ => count > 0,
+        };
+        if better {
+            let time = c.time();
+            best = Some((
+                Message::Values {
+                    name,
+                    values,
+                    count,
+                },
+                time,
+            ));
+        }
+    }
+    best
+}
+
+fn malformed<'a>(line: &'a str) -> Option<(Message<'a>, Option<Time<'a>>)> {
+    let (name, c) = splits(line, "~.", '@').into_iter().last()?;
+    let rest = &line[c.pos..];
+    let payload = rest[..rest.find('\n').unwrap_or(rest.len())].trim();
+    Some((Message::Malformed { name, payload }, None))
+}
+
+// key=value pairs of a keyed message
+pub struct Pairs<'a>(Cursor<'a>);
+
+impl<'a> Pairs<'a> {
+    pub fn new(pairs: &'a str) -> Pairs<'a> {
+        Pairs(Cursor::new(pairs, 0))
+    }
+}
+
+impl<'a> Iterator for Pairs<'a> {
+    type Item = (&'a str, &'a str);
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        let c = &mut self.0;
+        c.skip_space();
+        c.eat(b',');
+        c.skip_space();
+        c.pair()
+    }
+}
+
+// Numbers of a list payload along with the brackets and separators before each of them, the text after the last number
+// is left in the returned tail
+pub struct Numbers<'a> {
+    c: Cursor<'a>,
+}
+
+impl<'a> Numbers<'a> {
+    pub fn new(payload: &'a str) -> Numbers<'a> {
+        Numbers {
+            c: Cursor::new(payload, 0),
+        }
+    }
+    pub fn tail(&self) -> &'a str {
+        &self.c.s[self.c.pos..]
+    }
+}
+
+impl<'a> Iterator for Numbers<'a> {
+    type Item = (&'a str, &'a str); // Text between the previous number and this one, the number
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        let c = &mut self.c;
+        let start = c.pos;
+        while c.pos < c.s.len() {
+            let before = c.pos;
+            if let Some(n) = c.number() {
+                return Some((&c.s[start..before], n));
+            }
+            c.pos += 1; // Payload only holds ascii between numbers
+        }
+        c.pos = start;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(line: &str) -> Option<(bool, String, String)> {
+        let (_, t) = message(line)?;
+        t.map(|t| (t.relative, String::from(t.value), String::from(t.unit)))
+    }
+
+    #[test]
+    fn not_a_message() {
+        assert!(message("plain text").is_none());
+        assert!(message("~ alone").is_none());
+        assert!(message("~.noat").is_none());
+        assert!(message("~!").is_none());
+    }
+
+    #[test]
+    fn values() {
+        match message("~.a@1,-2.5e3 , .5") {
+            Some((
+                Message::Values {
+                    name,
+                    values,
+                    count,
+                },
+                None,
+            )) => {
+                assert_eq!((name, count), ("a", 3));
+                assert_eq!(values, ["1", "-2.5e3", ".5"]);
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn extra_values_are_counted() {
+        match message("~.a@1,2,3,4,5") {
+            Some((Message::Values { values, count, .. }, _)) => {
+                assert_eq!((values, count), (["1", "2", "3"], 5))
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn number_edges() {
+        let first = |line| match message(line) {
+            Some((Message::Values { values, .. }, _)) => values[0],
+            m => panic!("{:?}", m),
+        };
+        assert_eq!(first("~.a@1."), "1"); // A dot without digits after it is not part of the number
+        assert_eq!(first("~.a@1e"), "1");
+        assert_eq!(first("~.a@+1E+2"), "+1E+2");
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(time("~.a@1"), None);
+        let t = |r, v: &str, u: &str| Some((r, String::from(v), String::from(u)));
+        assert_eq!(time("~.a@1 t=5"), t(false, "5", ""));
+        assert_eq!(time("~.a@1,t=+2.5ms"), t(true, "2.5", "ms"));
+        assert_eq!(time("~.a@k=1, t=3ns"), t(false, "3", "ns"));
+        assert_eq!(time("~!b@t=4us"), t(false, "4", "us"));
+        assert_eq!(time("~%g@1[2] t=1s"), t(false, "1", "s"));
+    }
+
+    #[test]
+    fn keyed() {
+        match message("~.m@rpm=5, temp.c = -2 t=1") {
+            Some((Message::Keyed { name, pairs }, Some(_))) => {
+                assert_eq!(name, "m");
+                let got: Vec<(&str, &str)> = Pairs::new(pairs).collect();
+                assert_eq!(got, vec![("rpm", "5"), ("temp.c", "-2")]);
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn keyed_needs_every_pair() {
+        // Text after the pairs makes it not a keyed message, and nothing else fits either
+        match message("~.m@a=1 trailing") {
+            Some((Message::Malformed { name, payload }, None)) => {
+                assert_eq!((name, payload), ("m", "a=1 trailing"))
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn time_is_not_a_key() {
+        match message("~.x@t=5") {
+            Some((Message::Malformed { name, payload }, None)) => {
+                assert_eq!((name, payload), ("x", "t=5"))
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn list() {
+        match message("~#l#2,2@(1,2)(3,4) t=1") {
+            Some((
+                Message::List {
+                    name,
+                    shape,
+                    payload,
+                },
+                Some(_),
+            )) => {
+                assert_eq!(
+                    (name, shape, payload),
+                    ("l", Some(("2", "2")), "(1,2)(3,4) ")
+                );
+            }
+            m => panic!("{:?}", m),
+        }
+        match message("~#a#b#@1,2") {
+            Some((Message::List { name, shape, .. }, None)) => {
+                assert_eq!((name, shape), ("a#b", None))
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        let mut n = Numbers::new("((1, 2),(3))x");
+        let got: Vec<(&str, &str)> = n.by_ref().collect();
+        assert_eq!(got, vec![("((", "1"), (", ", "2"), ("),(", "3")]);
+        assert_eq!(n.tail(), "))x");
+    }
+
+    #[test]
+    fn node() {
+        match message("~%g@12[1, 2,3]") {
+            Some((Message::Node { name, id, children }, None)) => {
+                assert_eq!((name, id, children), ("g", "12", "1, 2,3"))
+            }
+            m => panic!("{:?}", m),
+        }
+        assert!(!matches!(
+            message("~%g@12[1"),
+            Some((Message::Node { .. }, _))
+        ));
+    }
+
+    #[test]
+    fn breakpoint() {
+        for line in &["~!stop", "~!stop here", "~!stop,x", "~!stop@t=1"] {
+            match message(line) {
+                Some((Message::Break { name }, _)) => assert_eq!(name, "stop"),
+                m => panic!("{:?}", m),
+            }
+        }
+    }
+
+    #[test]
+    fn later_forms_win() {
+        // Fits both a list and values, lists are further down the grammar
+        match message("~.v@2 ~#l#@1") {
+            Some((Message::List { name, .. }, _)) => assert_eq!(name, "l"),
+            m => panic!("{:?}", m),
+        }
+        match message("~.v@2 ~!b") {
+            Some((Message::Break { name }, _)) => assert_eq!(name, "b"),
+            m => panic!("{:?}", m),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+// Recording of the text input of a session and replaying it later. A session file holds every line as it reached the
+// parser, one per line with the time it was read, its number in the stream and where it came from:
+//
+//   # scope session
+//   <time in us since start>\t<line number>\t<origin, - for stdin>\t<text>
+//
+// A replay feeds the lines back through the parser with the times they were recorded at, so it plots like the session
+// did. A seek back carries on from the time of the last line sent, so replayed times only go forward and points
+// plotted before a seek stay where they are, device times given in the text are replayed as recorded. Samples from
+// binary sources are decoded before they reach the parser and are not recorded
+use channel::{self, RecvTimeoutError};
+use ingest::duration2us;
+use queue::Sender;
+use source::{Data, Line, CHUNK_LINES};
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HEADER: &str = "# scope session";
+
+pub struct Recorder {
+    out: BufWriter<File>,
+    warned: bool, // Binary samples can not be recorded, said so once
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> io::Result<Recorder> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        Ok(Recorder { out, warned: false })
+    }
+
+    pub fn write(&mut self, line: &Line) -> io::Result<()> {
+        let text = match line.data {
+            Data::Text(ref t) => t,
+            Data::Samples(_) => {
+                if !self.warned {
+                    println!("Binary input is not recorded");
+                    self.warned = true;
+                }
+                return Ok(());
+            }
+        };
+        let time = duration2us(&line.time);
+        let origin = line.origin.as_ref().map_or("-", |o| o.as_str());
+        for (i, l) in text.lines().enumerate() {
+            writeln!(self.out, "{}\t{}\t{}\t{}", time, line.number + i, origin, l)?;
+        }
+        self.out.flush() // Keep what was recorded when scope is killed
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Record {
+    time: f64,
+    number: usize,
+    origin: Option<String>,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    Real,
+    Scaled(f64), // Times faster than it was recorded
+    Fast,        // As fast as the parser takes lines
+}
+
+impl FromStr for Speed {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Speed, String> {
+        match s {
+            "real" => Ok(Speed::Real),
+            "fast" => Ok(Speed::Fast),
+            _ => match s.trim_end_matches('x').parse::<f64>() {
+                Ok(f) if f > 0. => Ok(Speed::Scaled(f)),
+                _ => Err(format!(
+                    "Unknown replay speed {:?}, use real, fast or a factor",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Control {
+    Pause,
+    Play,
+    Seek(f64), // us since the start of the session
+    Speed(Speed),
+}
+
+pub struct Session {
+    records: Vec<Record>,
+}
+
+impl Session {
+    pub fn load(path: &str) -> Result<Session, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut records = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("{}: {}", path, e))?;
+            if i == 0 && line == HEADER {
+                continue;
+            }
+            let bad = || format!("{}:{}: not a session record", path, i + 1);
+            let mut fields = line.splitn(4, '\t');
+            let time = fields.next().and_then(|t| t.parse().ok()).ok_or_else(bad)?;
+            let number = fields.next().and_then(|n| n.parse().ok()).ok_or_else(bad)?;
+            let origin = match fields.next().ok_or_else(bad)? {
+                "-" => None,
+                o => Some(String::from(o)),
+            };
+            let text = String::from(fields.next().ok_or_else(bad)?);
+            records.push(Record {
+                time,
+                number,
+                origin,
+                text,
+            });
+        }
+        Ok(Session { records })
+    }
+
+    fn start(&self) -> f64 {
+        self.records.first().map_or(0., |r| r.time)
+    }
+
+    // Length of the session in us
+    pub fn length(&self) -> f64 {
+        self.records.last().map_or(0., |r| r.time) - self.start()
+    }
+
+    // Index of the first record at or after a time since the start
+    fn find(&self, time: f64) -> usize {
+        let time = self.start() + time;
+        self.records.partition_point(|r| r.time < time)
+    }
+
+    // Records from i that were read together, returned as one line of at most CHUNK_LINES lines read shift us later
+    // than recorded
+    fn chunk(&self, i: usize, shift: f64) -> (Line, usize) {
+        let first = &self.records[i];
+        let mut end = i + 1;
+        let mut text = first.text.clone() + "\n";
+        while let Some(r) = self.records.get(end) {
+            if end - i == CHUNK_LINES
+                || r.time != first.time
+                || r.origin != first.origin
+                || r.number != first.number + end - i
+            {
+                break;
+            }
+            text.push_str(&r.text);
+            text.push('\n');
+            end += 1;
+        }
+        let line = Line {
+            time: Duration::from_nanos(((first.time + shift) * 1e3) as u64),
+            data: Data::Text(text),
+            number: first.number,
+            origin: first.origin.clone(),
+        };
+        (line, end)
+    }
+
+    // Plays the session on its own thread, the returned player controls it
+    pub fn spawn(self, speed: Speed, tx: Sender<Line>) -> Player {
+        let (control, rx) = channel::unbounded();
+        let player = Player {
+            control,
+            position: Arc::new(AtomicU64::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
+            length: self.length(),
+        };
+        let position = player.position.clone();
+        let paused = player.paused.clone();
+        thread::spawn(move || {
+            let mut speed = speed;
+            let mut next = 0;
+            // Session time of the next record was due at this instant
+            let mut base = (Instant::now(), self.start());
+            let mut stopped = false;
+            let mut shift = 0.; // Added to the times of records sent, so they never go back on a seek
+            let mut last = 0.; // Time the last line was sent with
+            loop {
+                let done = next >= self.records.len();
+                let wait = if stopped || done {
+                    None
+                } else {
+                    let ahead = self.records[next].time - base.1;
+                    let us = match speed {
+                        Speed::Real => ahead,
+                        Speed::Scaled(f) => ahead / f,
+                        Speed::Fast => 0.,
+                    };
+                    let due = base.0 + Duration::from_micros(us.max(0.) as u64);
+                    Some(due.saturating_duration_since(Instant::now()))
+                };
+                let cmd = match wait {
+                    Some(w) if w == Duration::from_secs(0) => rx.try_recv().ok(),
+                    Some(w) => match rx.recv_timeout(w) {
+                        Ok(c) => Some(c),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    },
+                    None => match rx.recv() {
+                        Ok(c) => Some(c),
+                        Err(_) => return,
+                    },
+                };
+                let resume_at =
+                    |i: usize| (Instant::now(), self.records.get(i).map_or(0., |r| r.time));
+                match cmd {
+                    Some(Control::Pause) => stopped = true,
+                    Some(Control::Play) => {
+                        stopped = false;
+                        base = resume_at(next);
+                    }
+                    Some(Control::Seek(t)) => {
+                        next = self.find(t);
+                        base = resume_at(next);
+                        if let Some(r) = self.records.get(next) {
+                            shift = (last - r.time).max(0.);
+                        }
+                        position.store(t.max(0.).min(self.length()).to_bits(), Ordering::Relaxed);
+                    }
+                    Some(Control::Speed(s)) => {
+                        speed = s;
+                        base = resume_at(next);
+                    }
+                    None => {
+                        let (line, end) = self.chunk(next, shift);
+                        last = self.records[next].time + shift;
+                        position.store(
+                            (self.records[next].time - self.start()).to_bits(),
+                            Ordering::Relaxed,
+                        );
+                        next = end;
+                        if tx.send(line).is_err() {
+                            println!("Replay lost a line, nothing is parsing input");
+                        }
+                        if next >= self.records.len() {
+                            println!("Replay finished");
+                        }
+                        continue;
+                    }
+                }
+                paused.store(stopped, Ordering::Relaxed);
+            }
+        });
+        player
+    }
+}
+
+// Handle on a replay running on its own thread
+pub struct Player {
+    control: channel::Sender<Control>,
+    position: Arc<AtomicU64>, // Bits of the us since the start of the last line sent
+    paused: Arc<AtomicBool>,
+    pub length: f64,
+}
+
+impl Player {
+    pub fn send(&self, c: Control) {
+        let _ = self.control.send(c);
+    }
+
+    pub fn position(&self) -> f64 {
+        f64::from_bits(self.position.load(Ordering::Relaxed))
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use queue::{self, Overload, Receiver, Stats, Weight};
+
+    fn session(times: &[f64]) -> Session {
+        let records = times
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| Record {
+                time,
+                number: i,
+                origin: None,
+                text: format!("~.a@{}", i),
+            })
+            .collect();
+        Session { records }
+    }
+
+    fn times(rx: &Receiver<Line>, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|_| duration2us(&rx.recv_timeout(Duration::from_secs(5)).unwrap().time))
+            .collect()
+    }
+
+    #[test]
+    fn records_read_together_are_chunked() {
+        let s = session(&[1., 1., 2.]);
+        let (line, end) = s.chunk(0, 0.);
+        assert_eq!(end, 2);
+        assert_eq!(line.weight(), 2);
+        let (line, end) = s.chunk(2, 10.);
+        assert_eq!(end, 3);
+        assert_eq!(duration2us(&line.time), 12.);
+    }
+
+    #[test]
+    fn seeking_back_carries_on_from_the_last_time() {
+        let (tx, rx) = queue::bounded(100, Overload::Block, &Stats::default());
+        let player = session(&[100., 200., 300.]).spawn(Speed::Fast, tx);
+        assert_eq!(times(&rx, 3), vec![100., 200., 300.]);
+        player.send(Control::Seek(100.));
+        assert_eq!(times(&rx, 2), vec![300., 400.]);
+        player.send(Control::Seek(0.));
+        assert_eq!(times(&rx, 3), vec![400., 500., 600.]);
+    }
+
+    #[test]
+    fn seeking_forward_keeps_recorded_times() {
+        let (tx, rx) = queue::bounded(100, Overload::Block, &Stats::default());
+        let player = session(&[0., 1e9, 2e9]).spawn(Speed::Real, tx);
+        assert_eq!(times(&rx, 1), vec![0.]);
+        player.send(Control::Seek(2e9));
+        assert_eq!(times(&rx, 1), vec![2e9]);
+    }
+}
@@ -6,12 +6,22 @@ use std::f64::NAN;
 use std::rc::{Rc, Weak};
 // use std::mem::size_of;
 // use std::marker::Sized;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::mem::size_of;
+use std::time::Instant;
 
 use self::color_set::{Color, Generator};
 
 // mod drawstyles;
 use drawstyles::*;
+use export::Row;
+use lod::{Extreme, Pyramid};
+
+use log::{Log, SourceLines};
+pub use scope::ingest::{MsgPoint, ParseError, PointType};
+use scope::queue::{Overload, Stats};
+use scope::session::Player;
+use scope::spill::{Budget, Store};
 
 pub type Rect = (f64, f64, f64, f64);
 
@@ -23,21 +33,52 @@ pub enum SignalHealth {
     InvalidFormat,
 }
 
-pub struct MsgPoint {
-    pub name: String,
-    pub line_number: usize,
-    pub timestamp: f64,
-    pub ty: PointType,
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
+// Health of a signal along with what went wrong, stays bad until cleared with the health command
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub state: SignalHealth,
+    pub errors: usize, // Lines and points addressed to the signal that were rejected
+    pub last_error: Option<String>,
 }
 
-pub enum PointType {
-    BreakPoint,
-    D1,
-    D2,
-    D3,
+impl Health {
+    fn new() -> Health {
+        Health {
+            state: SignalHealth::Good,
+            errors: 0,
+            last_error: None,
+        }
+    }
+    pub fn report(&mut self, error: String) {
+        self.state = SignalHealth::InvalidFormat;
+        self.errors += 1;
+        self.last_error = Some(error);
+    }
+    pub fn clear(&mut self) {
+        *self = Health::new();
+    }
+}
+
+// Why a point can not be plotted by a signal that takes points of the kinds in accepts
+fn check_point(point: &MsgPoint, accepts: &[PointType]) -> Result<(), String> {
+    if !accepts.contains(&point.ty) {
+        return Err(format!(
+            "{:?} point sent to a {:?} signal",
+            point.ty, accepts[0]
+        ));
+    }
+    let mut values = match point.ty {
+        PointType::BreakPoint => vec![],
+        PointType::D1 | PointType::Node => vec![point.x],
+        PointType::D2 => vec![point.x, point.y],
+        PointType::D3 => vec![point.x, point.y, point.z],
+        PointType::List => vec![],
+    };
+    values.extend_from_slice(&point.values);
+    if values.iter().any(|v| !v.is_finite()) || point.device_time.is_infinite() {
+        return Err(format!("non-finite value in {:?} point", point.ty));
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -52,7 +93,9 @@ pub trait Axes<T>: std::ops::Index<usize, Output = <Self as Axes<T>>::AxesUnit>
     fn default() -> T;
     fn ones() -> T;
     fn as_vec(&self) -> Vec<f64>;
+    fn from_slice(v: &[f64]) -> T;
     fn into(point: MsgPoint) -> Point<T>;
+    fn set_timestamp(&mut self, t: f64);
     fn timestamp() -> usize;
     fn x() -> usize;
     fn y() -> usize;
@@ -72,10 +115,23 @@ impl Axes<D1> for D1 {
     fn as_vec(&self) -> Vec<f64> {
         self.0.to_vec()
     }
+    fn from_slice(v: &[f64]) -> D1 {
+        let mut axes = [NAN; 2];
+        axes.copy_from_slice(&v[..2]);
+        D1 { 0: axes }
+    }
     fn into(point: MsgPoint) -> Point<D1> {
-        Point::new(D1 {
-            0: [point.timestamp, point.x],
-        })
+        Point::new(
+            D1 {
+                0: [point.timestamp, point.x],
+            },
+            point.timestamp,
+            point.device_time,
+            point.line_number,
+        )
+    }
+    fn set_timestamp(&mut self, t: f64) {
+        self.0[Self::timestamp()] = t;
     }
     fn timestamp() -> usize {
         0
@@ -104,10 +160,23 @@ impl Axes<D2> for D2 {
     fn as_vec(&self) -> Vec<f64> {
         self.0.to_vec()
     }
+    fn from_slice(v: &[f64]) -> D2 {
+        let mut axes = [NAN; 3];
+        axes.copy_from_slice(&v[..3]);
+        D2 { 0: axes }
+    }
     fn into(point: MsgPoint) -> Point<D2> {
-        Point::new(D2 {
-            0: [point.timestamp, point.x, point.y],
-        })
+        Point::new(
+            D2 {
+                0: [point.timestamp, point.x, point.y],
+            },
+            point.timestamp,
+            point.device_time,
+            point.line_number,
+        )
+    }
+    fn set_timestamp(&mut self, t: f64) {
+        self.0[Self::timestamp()] = t;
     }
     fn timestamp() -> usize {
         0
@@ -136,10 +205,23 @@ impl Axes<D3> for D3 {
     fn as_vec(&self) -> Vec<f64> {
         self.0.to_vec()
     }
+    fn from_slice(v: &[f64]) -> D3 {
+        let mut axes = [NAN; 4];
+        axes.copy_from_slice(&v[..4]);
+        D3 { 0: axes }
+    }
     fn into(point: MsgPoint) -> Point<D3> {
-        Point::new(D3 {
-            0: [point.timestamp, point.x, point.y, point.z],
-        })
+        Point::new(
+            D3 {
+                0: [point.timestamp, point.x, point.y, point.z],
+            },
+            point.timestamp,
+            point.device_time,
+            point.line_number,
+        )
+    }
+    fn set_timestamp(&mut self, t: f64) {
+        self.0[Self::timestamp()] = t;
     }
     fn timestamp() -> usize {
         0
@@ -177,11 +259,35 @@ impl std::ops::Index<usize> for D3 {
 #[derive(Debug, Clone, Copy)]
 pub struct Point<A> {
     pub axes: A,
+    pub received: f64, // Both clocks are kept so a signal can switch between them, see Clock
+    pub device: f64,
+    pub line: usize, // Line of input the point was read from
 }
 
 impl<A> Point<A> {
-    fn new(axes: A) -> Point<A> {
-        Point { axes }
+    fn new(axes: A, received: f64, device: f64, line: usize) -> Point<A> {
+        Point {
+            axes,
+            received,
+            device,
+            line,
+        }
+    }
+}
+
+// Which time a signal plots its points against
+#[derive(Debug, Clone, Copy)]
+pub enum Clock {
+    Device, // Time supplied by the producer, falling back to receive time for lines that did not give one
+    Received, // Time the line was read
+}
+
+impl Clock {
+    fn pick(&self, received: f64, device: f64) -> f64 {
+        match self {
+            Clock::Device if !device.is_nan() => device,
+            _ => received,
+        }
     }
 }
 
@@ -229,67 +335,262 @@ impl Range {
     }
 }
 
-//This class supports constant time fifo buffer, and tracks minimums and maximum values
-//In the future it may be posible to get minimums and maximums in constant time as well
-//Using https://stackoverflow.com/questions/4802038/implement-a-queue-in-which-push-rear-pop-front-and-get-min-are-all-consta
-#[derive(Debug)]
+// Fifo buffer of points that keeps the range of every axis as points are pushed and popped, both in amortised constant
+// time. Each axis has a queue of the points that could still become its minimum, increasing from the front, and
+// likewise for the maximum (https://stackoverflow.com/questions/4802038). A point pushed drops every point behind it
+// that can no longer be the extreme while it is kept, so the front is always the extreme of what is left.
+// With a memory budget the oldest points can be written out in chunks, the signal manager picks whose when the budget
+// is exceeded. They are read back from the spill store when asked for, so points are handed out by value
 pub struct RangedDeque<A> {
+    spilled: Option<Store>, // Points older than any in memory, set along with the budget
     points: VecDeque<Point<A>>,
-    range: Range,
+    popped: usize, // Points popped so far, which is the sequence number of the front point
+    mins: Vec<VecDeque<(usize, f64)>>, // Sequence number and value on each axis
+    maxs: Vec<VecDeque<(usize, f64)>>,
 }
 
+const SPILL_CHUNK: usize = 1 << 16; // Most points written out at once
+
 impl<A> RangedDeque<A>
 where
     A: Axes<A> + Clone,
 {
     fn new() -> RangedDeque<A> {
         RangedDeque {
-            points: std::collections::VecDeque::new(),
-            range: Range {
-                min: A::default().as_vec(),
-                max: A::default().as_vec(),
-            },
+            spilled: None,
+            points: VecDeque::new(),
+            popped: 0,
+            mins: vec![VecDeque::new(); A::size()],
+            maxs: vec![VecDeque::new(); A::size()],
+        }
+    }
+    // Empty deque spilling to the same budget
+    fn empty_like(&self) -> RangedDeque<A> {
+        let mut d = RangedDeque::new();
+        if let Some(ref s) = self.spilled {
+            d.set_budget(s.budget().clone());
+        }
+        d
+    }
+    fn set_budget(&mut self, budget: Rc<Budget>) {
+        if self.spilled.is_some() {
+            return; // Points already spilled stay with their budget
+        }
+        budget.charge(self.points.len() * size_of::<Point<A>>());
+        self.spilled = Some(Store::new(budget, A::size() + 3));
+    }
+    // Bytes of points in memory charged to the budget
+    fn resident(&self) -> usize {
+        match self.spilled {
+            Some(_) => self.points.len() * size_of::<Point<A>>(),
+            None => 0,
         }
     }
-    fn push(&mut self, pt: Point<A>) -> bool {
-        //TODO: recompupte range
-        // println!("{:?}", (pt.axes[0].clone().into(), pt.axes[1].clone().into()));
-        let mut range_update = false;
+    // Write out up to a chunk of the oldest points in memory, false if there was nothing to write or it failed
+    fn spill(&mut self) -> bool {
+        let store = match self.spilled {
+            Some(ref mut s) => s,
+            None => return false,
+        };
+        let n = self.points.len().min(SPILL_CHUNK);
+        if n == 0 {
+            return false;
+        }
+        let mut records = Vec::with_capacity(n * (A::size() + 3));
+        for pt in self.points.iter().take(n) {
+            records.extend(pt.axes.as_vec());
+            records.extend(&[pt.received, pt.device, pt.line as f64]);
+        }
+        if let Err(e) = store.push(&records) {
+            println!(
+                "Could not spill points to disk, keeping them in memory: {}",
+                e
+            );
+            store.budget().set_limit(None);
+            return false;
+        }
+        self.points.drain(..n);
+        store.budget().release(n * size_of::<Point<A>>());
+        true
+    }
+    fn spilled_len(&self) -> usize {
+        self.spilled.as_ref().map_or(0, |s| s.len())
+    }
+    fn budget(&self) -> Option<&Rc<Budget>> {
+        self.spilled.as_ref().map(|s| s.budget())
+    }
+    fn push(&mut self, pt: Point<A>) {
+        let seq = self.popped + self.len();
         for i in 0..A::size() {
-            let t = pt.axes[i].clone().into();
-            if self.range.min[i] > t || self.range.min[i].is_nan() {
-                self.range.min[i] = t;
-                range_update = true;
+            let t: f64 = pt.axes[i].clone().into();
+            if t.is_nan() {
+                continue;
             }
-            if self.range.max[i] < t || self.range.max[i].is_nan() {
-                self.range.max[i] = t;
-                range_update = true;
+            let mins = &mut self.mins[i];
+            while mins.back().is_some_and(|m| m.1 >= t) {
+                mins.pop_back();
             }
+            mins.push_back((seq, t));
+            let maxs = &mut self.maxs[i];
+            while maxs.back().is_some_and(|m| m.1 <= t) {
+                maxs.pop_back();
+            }
+            maxs.push_back((seq, t));
         }
-        // println!("{:?}", self.range);
-        // println!("{:#?}", self.range);
         self.points.push_back(pt);
-        return range_update;
+        if let Some(ref s) = self.spilled {
+            s.budget().charge(size_of::<Point<A>>());
+        }
     }
-    pub fn get_last(&self) -> Option<&Point<A>> {
-        self.points.back()
+    pub fn get_last(&self) -> Option<Point<A>> {
+        match self.points.back() {
+            Some(pt) => Some(pt.clone()),
+            None => self.len().checked_sub(1).map(|i| self.get(i)),
+        }
     }
-    pub fn get(&self, idx: usize) -> &Point<A> {
-        &self.points[idx]
+    pub fn get_first(&self) -> Option<Point<A>> {
+        if self.len() == 0 {
+            None
+        } else {
+            Some(self.get(0))
+        }
+    }
+    pub fn get(&self, idx: usize) -> Point<A> {
+        let spilled = self.spilled_len();
+        if idx >= spilled {
+            return self.points[idx - spilled].clone();
+        }
+        let mut record = vec![NAN; A::size() + 3];
+        self.spilled.as_ref().unwrap().get(idx, &mut record);
+        let n = A::size();
+        Point::new(
+            A::from_slice(&record),
+            record[n],
+            record[n + 1],
+            record[n + 2] as usize,
+        )
     }
     pub fn len(&self) -> usize {
-        self.points.len()
+        self.spilled_len() + self.points.len()
     }
-    #[allow(dead_code)]
-    fn pop(&mut self) {
-        //TODO: Recompute range if popped val matches range
-        self.points.pop_front();
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // Drops the oldest point, a spilled one is skipped over in the store without being read back
+    fn pop(&mut self) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        match self.spilled {
+            Some(ref mut s) if !s.is_empty() => s.pop_front(),
+            Some(ref s) => {
+                self.points.pop_front();
+                s.budget().release(size_of::<Point<A>>());
+            }
+            None => {
+                self.points.pop_front();
+            }
+        }
+        let seq = self.popped;
+        self.popped += 1;
+        for q in self.mins.iter_mut().chain(self.maxs.iter_mut()) {
+            if q.front().is_some_and(|m| m.0 == seq) {
+                q.pop_front();
+            }
+        }
+        true
+    }
+    // Pops the points the history no longer has room for, returns how many
+    fn evict(&mut self, history: &History) -> usize {
+        let newest = self.get_last().map_or(NAN, |p| p.received);
+        let mut popped = 0;
+        while let Some(oldest) = self.get_first().map(|p| p.received) {
+            if !history.over(self.len(), oldest, newest) {
+                break;
+            }
+            self.pop();
+            popped += 1;
+        }
+        popped
     }
     pub fn get_range(&self) -> Range {
-        self.range.clone()
+        let front = |q: &VecDeque<(usize, f64)>| q.front().map_or(NAN, |m| m.1);
+        Range {
+            min: self.mins.iter().map(front).collect(),
+            max: self.maxs.iter().map(front).collect(),
+        }
+    }
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Point<A>> + 'a {
+        (0..self.len()).map(move |i| self.get(i))
     }
-    pub fn iter(&self) -> std::collections::vec_deque::Iter<Point<A>> {
-        self.points.iter()
+    // Index of the first point at or after value on an axis the points are sorted by
+    pub fn position(&self, axis: usize, value: f64) -> usize {
+        self.position_in(axis, value, 0, self.len())
+    }
+    // Same among the points lo up to hi, only those need to be sorted
+    pub fn position_in(&self, axis: usize, value: f64, mut lo: usize, mut hi: usize) -> usize {
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.get(mid).axes[axis].clone().into() < value {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+}
+
+impl<A> Drop for RangedDeque<A> {
+    fn drop(&mut self) {
+        if let Some(ref s) = self.spilled {
+            s.budget()
+                .release(self.points.len() * size_of::<Point<A>>());
+        }
+    }
+}
+
+// How much of its past a signal keeps, older points are dropped as new ones arrive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum History {
+    Unbounded,
+    Points(usize),
+    Seconds(f64), // Of receive time between the oldest and newest point
+}
+
+impl History {
+    // Whether the oldest of len points has to go
+    fn over(&self, len: usize, oldest: f64, newest: f64) -> bool {
+        match *self {
+            History::Unbounded => false,
+            History::Points(n) => len > n,
+            History::Seconds(s) => newest - oldest > s * 1e6,
+        }
+    }
+}
+
+impl std::str::FromStr for History {
+    type Err = String;
+    fn from_str(s: &str) -> Result<History, String> {
+        let bad = || {
+            format!(
+                "Unknown history {:?}, use a number of points, seconds such as 30s, or off",
+                s
+            )
+        };
+        if s == "off" {
+            return Ok(History::Unbounded);
+        }
+        if s.ends_with('s') {
+            return match s[..s.len() - 1].parse::<f64>() {
+                Ok(secs) if secs > 0. => Ok(History::Seconds(secs)),
+                _ => Err(bad()),
+            };
+        }
+        match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(History::Points(n)),
+            _ => Err(bad()),
+        }
     }
 }
 
@@ -300,6 +601,9 @@ struct ViewData {
     range: Range,
     maintain_aspect: bool,
     id: String,
+    window: Option<f64>, // us of time shown by time based signals, everything when None
+    follow: bool,        // Window ends at the newest point, released by panning
+    end: f64,            // Time the window ended at when last drawn, held while not following
 }
 impl ViewData {
     fn clear_range(&mut self) {
@@ -335,6 +639,9 @@ impl View {
             range: Range::new_cap(3),
             maintain_aspect: false,
             id: name,
+            window: None,
+            follow: true,
+            end: NAN,
         }));
         View {
             data,
@@ -371,12 +678,15 @@ impl View {
         }
     }
 
-    fn get_transform(&self, area: Rect, range: &Range) -> Transform {
-        let data = self.data.borrow();
+    // Signals plotted against time are timed, only they are windowed
+    fn get_transform(&self, area: Rect, range: &Range, timed: bool) -> Transform {
+        let mut data = self.data.borrow_mut();
 
-        let (xs, ys, _, xmax, ymin, ymax) = self.get_working_scale(&data, area, range);
+        let (xs, ys, _, xmax, ymin, ymax) = self.get_working_scale(&mut data, area, range, timed);
 
-        let dx = if AxisBind::X as u8 & self.mode != 0 {
+        let dx = if timed && data.window.is_some() && data.follow {
+            0. // Newest point stays at the right edge
+        } else if AxisBind::X as u8 & self.mode != 0 {
             data.pos.0
         } else {
             self.local_pos.0
@@ -388,28 +698,28 @@ impl View {
         };
 
         Transform {
-            dx: (area.2 - xmax * xs + dx) as f32,
-            dy: ((-ys * (ymax + ymin) / 2.) + (area.3 + area.1) / 2.0 + dy) as f32,
-            // dx: dx as f32, dy: dy as f32,
-            sx: xs as f32,
-            sy: ys as f32,
+            dx: area.2 - xmax * xs + dx,
+            dy: (-ys * (ymax + ymin) / 2.) + (area.3 + area.1) / 2.0 + dy,
+            sx: xs,
+            sy: ys,
             sz: 1.0,
         }
     }
 
     fn get_working_scale(
         &self,
-        data: &ViewData,
+        data: &mut ViewData,
         area: Rect,
         range: &Range,
+        timed: bool,
     ) -> (f64, f64, f64, f64, f64, f64) {
         let mut working_range = if AxisBind::X as u8 & self.mode != 0 {
             &data.range
         } else {
             range
         };
-        let xmin = working_range.min[0];
-        let xmax = working_range.max[0];
+        let mut xmin = working_range.min[0];
+        let mut xmax = working_range.max[0];
         working_range = if AxisBind::Y as u8 & self.mode != 0 {
             &data.range
         } else {
@@ -418,6 +728,15 @@ impl View {
         let ymin = working_range.min[1];
         let ymax = working_range.max[1];
 
+        if let (true, Some(window)) = (timed, data.window) {
+            if data.follow || data.end.is_nan() {
+                data.end = xmax;
+            } else {
+                xmax = data.end;
+            }
+            xmin = xmax - window;
+        }
+
         // println!("Working Range: x: ({:?}, {:?}), y: ({:?}, {:?})", xmin, xmax, ymin, ymax);
 
         let mut xs = ((area.2 - area.0) / (xmax - xmin)).max(MIN_SCALE);
@@ -471,7 +790,12 @@ impl View {
 
     // Takes screen position mouse dx and dy
     fn move_by(&mut self, by: (f64, f64), _area: Rect, _range: &Range) {
-        let data = &mut self.data.borrow_mut().pos;
+        let mut data = self.data.borrow_mut();
+        if data.window.is_some() && by.0 != 0. && data.follow {
+            data.follow = false; // Panning into history, the window stays where it was drawn last
+            println!("{}: stopped following the newest point", data.id);
+        }
+        let data = &mut data.pos;
         let x = if AxisBind::X as u8 & self.mode != 0 {
             &mut (data.0)
         } else {
@@ -489,17 +813,40 @@ impl View {
     fn set_bind_mode(&mut self, mode: u8) {
         self.mode = mode;
     }
+
+    // Show only the last window us of signals plotted against time, shared by every signal bound to the view
+    pub fn set_window(&mut self, window: Option<f64>) {
+        self.data.borrow_mut().window = window;
+        self.set_follow(true);
+    }
+
+    pub fn set_follow(&mut self, follow: bool) {
+        let mut data = self.data.borrow_mut();
+        data.follow = follow;
+        if follow {
+            data.pos.0 = 0.;
+            self.local_pos.0 = 0.;
+        }
+    }
+
+    pub fn following(&self) -> bool {
+        self.data.borrow().follow
+    }
 }
 
 struct Signal<'a, A> {
     name: String,
     color: Color,
-    unit_scale: Vec<f64>, //If axis values exceed that which can fit in f32, divide by these values and use these values for display
     points: RangedDeque<A>,
     style: Box<DrawStyle<A>>,
-    health: SignalHealth,
+    health: Health,
     view: View,
     pick_thresh: f32,
+    clock: Clock,
+    history: History,
+    lod: Option<Pyramid>, // Kept when plotted against time, the points are then drawn from it and not the style
+    unsorted: VecDeque<usize>, // Number in the pyramid of every point kept whose time is before the one ahead of it
+    charged: usize,            // Bytes of the pyramid or the style's vertices charged to the budget
     display: &'a glium::Display,
 }
 
@@ -516,41 +863,163 @@ where
         Signal {
             name: name.clone(),
             color: Generator::get_color(name.clone(), 0.8, 1.),
-            unit_scale: T::ones().as_vec(),
             points: RangedDeque::new(),
             style,
-            health: SignalHealth::Good,
+            health: Health::new(),
             view,
             pick_thresh: 0.1,
+            clock: Clock::Device,
+            history: History::Unbounded,
+            lod: Signal::<T>::new_lod(),
+            unsorted: VecDeque::new(),
+            charged: 0,
             display,
         }
     }
+    fn new_lod() -> Option<Pyramid> {
+        if T::x() == T::timestamp() {
+            Some(Pyramid::new())
+        } else {
+            None
+        }
+    }
     fn get_transform(&self, area: Rect) -> Transform {
         let range = self.style.get_range(&self.points); // this range in xy view space
 
-        self.view.get_transform(area, &range)
+        self.view
+            .get_transform(area, &range, T::x() == T::timestamp())
     }
     fn add_ds_point(&mut self, pt: &Point<T>) {
-        //TODO:  Do unit scaling here before pass to drawstyle
-        self.style
-            .push(&pt, &self.color, &self.points, self.display);
+        let (x, y) = (
+            pt.axes[T::x()].clone().into(),
+            pt.axes[T::y()].clone().into(),
+        );
+        let sorted = self.points.len() < 2
+            || self.points.get(self.points.len() - 2).axes[T::x()]
+                .clone()
+                .into()
+                <= x;
+        match self.lod {
+            Some(ref mut lod) => {
+                if !sorted {
+                    // Starts a new run of sorted times, each is looked up on its own
+                    self.unsorted.push_back(lod.pushed());
+                }
+                lod.push(x, y)
+            }
+            None => self.style.push(pt, &self.color, &self.points, self.display),
+        }
+    }
+    // Lowest and highest points of every pixel column of the area, or every point where there are few enough. Each run
+    // of sorted times is covered in turn, so points out of order are drawn where they fall
+    fn summary(&self, lod: &Pyramid, trans: &Transform, area: Rect) -> Vec<Extreme> {
+        let data_x = |screen: f64| (screen - trans.dx) / trans.sx;
+        let first = lod.live();
+        let width = self.display.get_framebuffer_dimensions().0;
+        let columns = ((area.2 - area.0) / 2. * width as f64).max(1.);
+        let raw = |seq: usize| {
+            let p = self.points.get(seq - first);
+            (p.axes[T::x()].clone().into(), p.axes[T::y()].clone().into())
+        };
+        let starts = self.unsorted.iter().map(|s| s - first);
+        let ends = starts.clone().chain(Some(self.points.len()));
+        let mut out = Vec::new();
+        for (lo, hi) in Some(0).into_iter().chain(starts).zip(ends) {
+            let a = self
+                .points
+                .position_in(T::x(), data_x(area.0), lo, hi)
+                .saturating_sub(1)
+                .max(lo);
+            let b = (self.points.position_in(T::x(), data_x(area.2), lo, hi) + 1).min(hi);
+            let level = lod.level_for(b.saturating_sub(a) as f64 / columns);
+            lod.cover(level, first + a, first + b, &raw, &mut out);
+        }
+        out
+    }
+    // Whatever draws the points holds memory of its own that grows and shrinks with them, the budget is told about it
+    // after every change. It can not be spilled, only the points themselves are
+    fn recharge(&mut self) {
+        let bytes = match self.lod {
+            Some(ref lod) => lod.bytes(),
+            None => self.style.bytes(),
+        };
+        if let Some(b) = self.points.budget() {
+            b.release(self.charged);
+            b.charge(bytes);
+            self.charged = bytes;
+        }
+    }
+    // Drop the points the history no longer has room for
+    fn evict(&mut self) {
+        for _ in 0..self.points.evict(&self.history) {
+            match self.lod {
+                Some(ref mut lod) => lod.pop_front(),
+                None => self.style.pop_front(),
+            }
+        }
+        if let Some(ref lod) = self.lod {
+            while self.unsorted.front().is_some_and(|&s| s <= lod.live()) {
+                self.unsorted.pop_front();
+            }
+        }
+    }
+}
+
+impl<'a, T> Drop for Signal<'a, T> {
+    fn drop(&mut self) {
+        if let Some(ref s) = self.points.spilled {
+            s.budget().release(self.charged);
+        }
+    }
+}
+
+fn new_style<T>(style: &Styles, display: &glium::Display) -> Box<DrawStyle<T>>
+where
+    T: Axes<T> + Clone,
+{
+    match style {
+        Styles::Scatter => Box::new(Scatter::new(display)),
+        Styles::Lines => Box::new(Lines::new(display)),
     }
 }
 
 pub trait GenericSignal {
     fn get_name(&self) -> &String;
     fn draw(&self, target: &mut glium::Frame, area: Rect);
-    fn add_point(&mut self, point: MsgPoint);
+    fn add_point(&mut self, point: MsgPoint) -> Result<(), String>; // Points that can not be plotted are rejected
     fn get_color(&self) -> Color;
-    fn get_health(&self) -> SignalHealth;
+    fn get_health(&self) -> &Health;
+    fn get_health_mut(&mut self) -> &mut Health;
     fn pick(&self, mouse: (f32, f32), area: Rect) -> Option<PickData>;
     fn get_point_strings(&self, idx: usize) -> (String, String, String);
     fn set_style(&mut self, style: &Styles);
+    fn set_clock(&mut self, clock: Clock);
+    fn set_history(&mut self, _history: History) {}
+    fn set_budget(&mut self, _budget: Rc<Budget>) {}
+    // Bytes of memory charged to the budget, and writing some of the oldest of them out to disk
+    fn resident(&self) -> usize {
+        0
+    }
+    fn spill(&mut self) -> bool {
+        false
+    }
     fn set_bind_mode(&mut self, mode: u8);
     fn get_view(&mut self) -> &mut View;
     fn share_view(&self);
     fn zoom_by(&mut self, by: f64, center: (f64, f64));
     fn move_view_by(&mut self, by: (f64, f64), area: Rect);
+    // Screen x of a moment for signals plotted against time
+    fn time_x(&self, _received: f64, _device: f64, _area: Rect) -> Option<f64> {
+        None
+    }
+    // Line number and receive time of the line a picked point was read from
+    fn source(&self, _idx: usize) -> Option<(usize, f64)> {
+        None
+    }
+    // Time and values of every point, or only of those drawn inside an area. None for signals not made of points
+    fn export(&self, _visible: Option<Rect>) -> Option<Vec<Row>> {
+        None
+    }
 }
 
 impl<'a, T> GenericSignal for Signal<'a, T>
@@ -560,42 +1029,115 @@ where
     fn draw(&self, target: &mut glium::Frame, area: Rect) {
         let trans = self.get_transform(area);
 
-        self.style.draw(&trans, target);
+        match self.lod {
+            Some(ref lod) => {
+                let xy: Vec<(f64, f64)> = self
+                    .summary(lod, &trans, area)
+                    .iter()
+                    .map(|e| (e.x, e.y))
+                    .collect();
+                self.style
+                    .draw_summary(&xy, &self.color, &trans, target, self.display);
+            }
+            None => self.style.draw(&trans, target),
+        }
     }
-    fn add_point(&mut self, point: MsgPoint) {
-        let pt = T::into(point);
+    fn add_point(&mut self, point: MsgPoint) -> Result<(), String> {
+        let accepts: &[PointType] = match T::size() {
+            2 => &[PointType::D1, PointType::BreakPoint],
+            3 => &[PointType::D2],
+            _ => &[PointType::D3],
+        };
+        check_point(&point, accepts)?;
+        let mut pt = T::into(point);
+        pt.axes
+            .set_timestamp(self.clock.pick(pt.received, pt.device));
         self.points.push(pt.clone());
         self.add_ds_point(&pt);
+        self.evict();
+        self.recharge();
+        Ok(())
     }
 
     fn pick(&self, mouse: (f32, f32), area: Rect) -> Option<PickData> {
+        if let Some(ref lod) = self.lod {
+            // Only what is drawn can be picked
+            let trans = self.get_transform(area);
+            let first = lod.live();
+            let mut best: Option<(f32, PickData)> = None;
+            for e in self.summary(lod, &trans, area) {
+                let (x, y) = trans.apply(e.x, e.y);
+                let pos = (x as f32, y as f32);
+                let d = (pos.0 - mouse.0).abs() + (pos.1 - mouse.1).abs();
+                if d <= self.pick_thresh && best.as_ref().map_or(true, |b| d < b.0) {
+                    let pick = PickData {
+                        index: e.seq - first,
+                        screen_pos: pos,
+                    };
+                    best = Some((d, pick));
+                }
+            }
+            return best.map(|b| b.1);
+        }
         self.style.pick(
             &self.points,
             mouse,
             self.get_transform(area),
-            self.unit_scale.clone(),
             self.pick_thresh,
         )
     }
     fn get_color(&self) -> Color {
         self.color
     }
-    fn get_health(&self) -> SignalHealth {
-        self.health.clone()
+    fn get_health(&self) -> &Health {
+        &self.health
+    }
+    fn get_health_mut(&mut self) -> &mut Health {
+        &mut self.health
     }
     fn get_point_strings(&self, idx: usize) -> (String, String, String) {
-        self.style.get_point_strs(self.points.get(idx))
+        self.style.get_point_strs(&self.points.get(idx))
     }
     fn set_style(&mut self, style: &Styles) {
         //TODO: full vbo construction
-        self.style = match style {
-            Styles::Scatter => Box::new(Scatter::new(self.display)),
-            Styles::Lines => Box::new(Lines::new(self.display)),
-        };
+        self.style = new_style(style, self.display);
+        if self.lod.is_some() {
+            return; // Drawn from the pyramid, the style holds no points
+        }
         for i in 0..self.points.len() {
-            let a = self.points.get(i).clone(); //TODO: gotta be a better way
-            self.add_ds_point(&a);
+            let a = self.points.get(i);
+            self.style.push(&a, &self.color, &self.points, self.display);
         }
+        self.recharge();
+    }
+    fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        let fresh = self.points.empty_like();
+        let old = std::mem::replace(&mut self.points, fresh);
+        self.style.clear();
+        self.lod = Signal::<T>::new_lod();
+        self.unsorted.clear();
+        for mut pt in old.iter() {
+            pt.axes.set_timestamp(clock.pick(pt.received, pt.device));
+            self.points.push(pt.clone());
+            self.add_ds_point(&pt);
+        }
+        self.recharge();
+    }
+    fn set_history(&mut self, history: History) {
+        self.history = history;
+        self.evict();
+        self.recharge();
+    }
+    fn set_budget(&mut self, budget: Rc<Budget>) {
+        self.points.set_budget(budget);
+        self.recharge();
+    }
+    fn resident(&self) -> usize {
+        self.points.resident()
+    }
+    fn spill(&mut self) -> bool {
+        self.points.spill()
     }
     fn set_bind_mode(&mut self, mode: u8) {
         self.view.set_bind_mode(mode);
@@ -616,6 +1158,429 @@ where
     fn get_name(&self) -> &String {
         &self.name
     }
+    fn time_x(&self, received: f64, device: f64, area: Rect) -> Option<f64> {
+        if T::x() != T::timestamp() {
+            return None;
+        }
+        let trans = self.get_transform(area);
+        let t = self.clock.pick(received, device);
+        Some(trans.apply(t, 0.).0)
+    }
+    // The index may have been picked a frame ago, points may have been dropped since
+    fn source(&self, idx: usize) -> Option<(usize, f64)> {
+        if idx >= self.points.len() {
+            return None;
+        }
+        let pt = self.points.get(idx);
+        Some((pt.line, pt.received))
+    }
+    fn export(&self, visible: Option<Rect>) -> Option<Vec<Row>> {
+        let trans = visible.map(|area| (area, self.get_transform(area)));
+        let mut rows = Vec::new();
+        for pt in self.points.iter() {
+            if let Some((area, ref t)) = trans {
+                let (x, y) = t.apply(
+                    pt.axes[T::x()].clone().into(),
+                    pt.axes[T::y()].clone().into(),
+                );
+                if x < area.0 || x > area.2 || y < area.1 || y > area.3 {
+                    continue;
+                }
+            }
+            let mut row = [NAN; 4];
+            for (r, v) in row.iter_mut().zip(pt.axes.as_vec()) {
+                *r = v;
+            }
+            rows.push(row);
+        }
+        Some(rows)
+    }
+}
+
+// Signal fed by list messages. Each message replaces the whole array, only the latest snapshot is kept and drawn with
+// every row as its own trace of value against column index, so there is no history to limit
+struct ListSignal<'a> {
+    name: String,
+    color: Color,
+    latest: Option<MsgPoint>,
+    rows: Vec<RangedDeque<D2>>,
+    styles: Vec<Box<DrawStyle<D2>>>,
+    style: Styles,
+    health: Health,
+    view: View,
+    pick_thresh: f32,
+    clock: Clock,
+    display: &'a glium::Display,
+}
+
+impl<'a> ListSignal<'a> {
+    fn new(name: String, view: View, display: &'a glium::Display) -> ListSignal<'a> {
+        ListSignal {
+            name: name.clone(),
+            color: Generator::get_color(name.clone(), 0.8, 1.),
+            latest: None,
+            rows: Vec::new(),
+            styles: Vec::new(),
+            style: Styles::Lines,
+            health: Health::new(),
+            view,
+            pick_thresh: 0.1,
+            clock: Clock::Device,
+            display,
+        }
+    }
+    fn get_range(&self) -> Range {
+        let mut range = Range::new_cap(2);
+        for (points, style) in self.rows.iter().zip(self.styles.iter()) {
+            range.expandby(&style.get_range(points));
+        }
+        range
+    }
+    fn cols(&self) -> usize {
+        self.rows.first().map_or(0, |r| r.len())
+    }
+    // Rebuild the displayed rows from the most recent snapshot
+    fn show_latest(&mut self) {
+        let snap = match self.latest {
+            Some(ref s) => s,
+            None => return,
+        };
+        let (rows, cols) = snap.shape;
+        let time = self.clock.pick(snap.timestamp, snap.device_time);
+        while self.styles.len() < rows {
+            self.styles.push(new_style(&self.style, self.display));
+        }
+        self.styles.truncate(rows);
+        self.rows.clear();
+        for (r, style) in self.styles.iter_mut().enumerate() {
+            style.clear();
+            let mut points = RangedDeque::new();
+            for c in 0..cols {
+                let pt = Point::new(
+                    D2 {
+                        0: [time, c as f64, snap.values[r * cols + c]],
+                    },
+                    snap.timestamp,
+                    snap.device_time,
+                    snap.line_number,
+                );
+                points.push(pt.clone());
+                style.push(&pt, &self.color, &points, self.display);
+            }
+            self.rows.push(points);
+        }
+    }
+}
+
+impl<'a> GenericSignal for ListSignal<'a> {
+    fn draw(&self, target: &mut glium::Frame, area: Rect) {
+        let trans = self.view.get_transform(area, &self.get_range(), false);
+        for style in self.styles.iter() {
+            style.draw(&trans, target);
+        }
+    }
+    fn add_point(&mut self, point: MsgPoint) -> Result<(), String> {
+        check_point(&point, &[PointType::List])?;
+        self.latest = Some(point);
+        self.show_latest();
+        Ok(())
+    }
+    fn pick(&self, mouse: (f32, f32), area: Rect) -> Option<PickData> {
+        let trans = self.view.get_transform(area, &self.get_range(), false);
+        let mut best: Option<(f32, PickData)> = None;
+        for (r, (points, style)) in self.rows.iter().zip(self.styles.iter()).enumerate() {
+            if let Some(pick) = style.pick(points, mouse, trans, self.pick_thresh) {
+                let d = (pick.screen_pos.0 - mouse.0).abs() + (pick.screen_pos.1 - mouse.1).abs();
+                if best.as_ref().map_or(true, |b| d < b.0) {
+                    let index = r * self.cols() + pick.index;
+                    best = Some((
+                        d,
+                        PickData {
+                            index,
+                            screen_pos: pick.screen_pos,
+                        },
+                    ));
+                }
+            }
+        }
+        best.map(|b| b.1)
+    }
+    fn get_color(&self) -> Color {
+        self.color
+    }
+    fn get_health(&self) -> &Health {
+        &self.health
+    }
+    fn get_health_mut(&mut self) -> &mut Health {
+        &mut self.health
+    }
+    fn get_point_strings(&self, idx: usize) -> (String, String, String) {
+        let cols = self.cols();
+        let (r, c) = (idx / cols, idx % cols);
+        let (x, y, z) = self.styles[r].get_point_strs(&self.rows[r].get(c));
+        if self.rows.len() > 1 {
+            (format!("{}, {}", r, x), y, z)
+        } else {
+            (x, y, z)
+        }
+    }
+    fn source(&self, _idx: usize) -> Option<(usize, f64)> {
+        self.latest.as_ref().map(|s| (s.line_number, s.timestamp))
+    }
+    fn set_style(&mut self, style: &Styles) {
+        self.style = *style;
+        self.styles.clear();
+        self.show_latest();
+    }
+    fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        self.show_latest();
+    }
+    fn set_bind_mode(&mut self, mode: u8) {
+        self.view.set_bind_mode(mode);
+    }
+    fn get_view(&mut self) -> &mut View {
+        &mut self.view
+    }
+    fn share_view(&self) {
+        self.view.share(&self.get_range())
+    }
+    fn zoom_by(&mut self, by: f64, center: (f64, f64)) {
+        self.view.zoom(by, center);
+    }
+    fn move_view_by(&mut self, by: (f64, f64), area: Rect) {
+        let range = self.get_range();
+        self.view.move_by(by, area, &range);
+    }
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+pub struct GraphNode {
+    pub children: Vec<u64>,
+    pub updated: Option<Instant>, // None until the node has sent a message of its own rather than just being named as a child
+    pub timestamp: f64,
+    pub received: f64,
+    pub line_number: usize,
+}
+
+// Signal fed by node messages, each message sets the children of one node in a directed graph
+struct GraphSignal<'a> {
+    name: String,
+    color: Color,
+    nodes: BTreeMap<u64, GraphNode>,
+    style: Graph,
+    health: Health,
+    view: View,
+    pick_thresh: f32,
+    clock: Clock,
+    display: &'a glium::Display,
+}
+
+impl<'a> GraphSignal<'a> {
+    fn new(name: String, view: View, display: &'a glium::Display) -> GraphSignal<'a> {
+        GraphSignal {
+            name: name.clone(),
+            color: Generator::get_color(name.clone(), 0.8, 1.),
+            nodes: BTreeMap::new(),
+            style: Graph::new(display),
+            health: Health::new(),
+            view,
+            pick_thresh: 0.1,
+            clock: Clock::Device,
+            display,
+        }
+    }
+}
+
+impl<'a> GenericSignal for GraphSignal<'a> {
+    fn draw(&self, target: &mut glium::Frame, area: Rect) {
+        let trans = self
+            .view
+            .get_transform(area, &self.style.get_range(), false);
+        self.style
+            .draw(&self.nodes, &self.color, &trans, target, self.display);
+    }
+    fn add_point(&mut self, point: MsgPoint) -> Result<(), String> {
+        check_point(&point, &[PointType::Node])?;
+        let children: Vec<u64> = point.values.iter().map(|c| *c as u64).collect();
+        for c in children.iter() {
+            self.nodes.entry(*c).or_insert(GraphNode {
+                children: Vec::new(),
+                updated: None,
+                timestamp: NAN,
+                received: NAN,
+                line_number: 0,
+            });
+        }
+        self.nodes.insert(
+            point.x as u64,
+            GraphNode {
+                children,
+                updated: Some(Instant::now()),
+                timestamp: self.clock.pick(point.timestamp, point.device_time),
+                received: point.timestamp,
+                line_number: point.line_number,
+            },
+        );
+        self.style.layout(&self.nodes);
+        Ok(())
+    }
+    fn pick(&self, mouse: (f32, f32), area: Rect) -> Option<PickData> {
+        let trans = self
+            .view
+            .get_transform(area, &self.style.get_range(), false);
+        self.style.pick(&self.nodes, mouse, trans, self.pick_thresh)
+    }
+    fn get_color(&self) -> Color {
+        self.color
+    }
+    fn get_health(&self) -> &Health {
+        &self.health
+    }
+    fn get_health_mut(&mut self) -> &mut Health {
+        &mut self.health
+    }
+    fn get_point_strings(&self, idx: usize) -> (String, String, String) {
+        match self.nodes.iter().nth(idx) {
+            Some((id, node)) => (
+                format!("{}", id),
+                format!("{:?}", node.children),
+                match node.updated {
+                    Some(_) => format!("{:.*} us", 3, node.timestamp),
+                    None => String::new(), // Only named as a child, it has no time yet
+                },
+            ),
+            None => (String::new(), String::new(), String::new()),
+        }
+    }
+    // Nodes only named as children have not been read from any line
+    fn source(&self, idx: usize) -> Option<(usize, f64)> {
+        match self.nodes.iter().nth(idx) {
+            Some((_, node)) if node.updated.is_some() => Some((node.line_number, node.received)),
+            _ => None,
+        }
+    }
+    fn set_style(&mut self, style: &Styles) {
+        println!(
+            "{:?} is a graph and can not be drawn as {:?}",
+            self.name, style
+        );
+    }
+    // Node times are only shown when hovering, nodes keep the time they were given until they are next updated
+    fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+    fn set_bind_mode(&mut self, mode: u8) {
+        self.view.set_bind_mode(mode);
+    }
+    fn get_view(&mut self) -> &mut View {
+        &mut self.view
+    }
+    fn share_view(&self) {
+        self.view.share(&self.style.get_range())
+    }
+    fn zoom_by(&mut self, by: f64, center: (f64, f64)) {
+        self.view.zoom(by, center);
+    }
+    fn move_view_by(&mut self, by: (f64, f64), area: Rect) {
+        let range = self.style.get_range();
+        self.view.move_by(by, area, &range);
+    }
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+// A breakpoint message that was received, kept so the moment can be marked on signals plotted against time
+#[derive(Debug, Clone)]
+pub struct Break {
+    pub name: String,
+    pub received: f64,
+    pub device: f64,
+    pub line_number: usize,
+    pub log_pos: usize, // Position in the log when the breakpoint arrived, lines before it were read before it
+}
+
+// Whether points reach the signals, like the run, stop and single buttons of a bench oscilloscope
+#[derive(Debug, Clone, PartialEq)]
+pub enum Acquire {
+    Run,
+    Stop, // Signals and views are left as they are, points are held until run
+    Single(Single),
+}
+
+// One capture that stops the display when done. Points are plotted as usual until the trigger fires, then for the
+// length of the window, everything after is held as in Stop
+#[derive(Debug, Clone, PartialEq)]
+pub struct Single {
+    pub trigger: Option<Trigger>, // None starts the window with the next point
+    pub window: f64,              // Receive time in us captured once triggered
+    start: Option<f64>,           // Receive time the trigger fired at
+}
+
+impl Single {
+    pub fn new(trigger: Option<Trigger>, window: f64) -> Single {
+        Single {
+            trigger,
+            window,
+            start: None,
+        }
+    }
+
+    pub fn triggered(&self) -> bool {
+        self.start.is_some()
+    }
+
+    // False once the point falls after the window
+    fn takes(&mut self, point: &MsgPoint) -> bool {
+        match self.start {
+            Some(start) => point.timestamp - start <= self.window,
+            None => {
+                let fired = match self.trigger {
+                    Some(ref mut t) => t.fires(point),
+                    None => true,
+                };
+                if fired {
+                    self.start = Some(point.timestamp);
+                }
+                true
+            }
+        }
+    }
+}
+
+// Fires when the first value of a signal crosses a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub signal: String,
+    pub level: f64,
+    pub rising: bool,
+    last: f64, // Previous value of the signal, NAN before the first
+}
+
+impl Trigger {
+    pub fn new(signal: String, level: f64, rising: bool) -> Trigger {
+        Trigger {
+            signal,
+            level,
+            rising,
+            last: NAN,
+        }
+    }
+
+    fn fires(&mut self, point: &MsgPoint) -> bool {
+        if *point.name != *self.signal || point.x.is_nan() {
+            return false;
+        }
+        let last = std::mem::replace(&mut self.last, point.x);
+        if self.rising {
+            last < self.level && point.x >= self.level
+        } else {
+            last > self.level && point.x <= self.level
+        }
+    }
 }
 
 pub struct SignalManager<'a> {
@@ -624,6 +1589,24 @@ pub struct SignalManager<'a> {
     selection: Option<String>,
     views: Vec<Weak<RefCell<ViewData>>>,
     pub point_count: usize,
+    pub queues: Stats, // Lines waiting on their way here as input or points, by producer
+    pub log: Log,
+    pub sources: SourceLines,
+    pub under_cursor: Vec<(String, usize)>, // Signal and index of every point picked by the cursor
+    pub player: Option<Player>,             // Replay feeding the input, if any
+    pub area: Rect,                         // Where the signals were last drawn
+    pub history: History,                   // Given to new signals
+    pub memory: Rc<Budget>, // Memory of the points of every signal and what draws them, past it points are spilled
+    pub break_on: bool,     // Breakpoints stop the display, otherwise they are only marked
+    unplotted: BTreeMap<String, Health>, // Health of names with errors but no signal yet, handed over once there is one
+    breaks: Vec<Break>,
+    paused: Option<usize>,             // Breakpoint the display is stopped at
+    held: VecDeque<(MsgPoint, usize)>, // Points received while stopped along with the log position at the time
+    pub hold: usize, // Most points held, past it the overload policy applies as to a full queue
+    pub overload: Overload,
+    held_offered: usize, // Points that came to be held, decimation drops every other one
+    held_dropped: usize,
+    acquire: Acquire,
 }
 
 impl<'a> SignalManager<'a> {
@@ -635,43 +1618,244 @@ impl<'a> SignalManager<'a> {
             selection: None,
             views: Vec::new(),
             point_count: 0,
+            queues: Stats::default(),
+            log: Log::new(10_000),
+            sources: SourceLines::new(0),
+            under_cursor: Vec::new(),
+            player: None,
+            area: (0., 0., 0., 0.),
+            history: History::Unbounded,
+            memory: Budget::new(None),
+            break_on: true,
+            unplotted: BTreeMap::new(),
+            breaks: Vec::new(),
+            paused: None,
+            held: VecDeque::new(),
+            hold: 100_000,
+            overload: Overload::Block,
+            held_offered: 0,
+            held_dropped: 0,
+            acquire: Acquire::Run,
         }
     }
 
     pub fn add_point(&mut self, point: MsgPoint) {
-        let name = point.name.clone();
-        match self.signals.entry(name.clone()) {
-            std::collections::hash_map::Entry::Occupied(mut val) => {
-                let mut ch = val.get_mut();
-                ch.add_point(point);
-            }
-            std::collections::hash_map::Entry::Vacant(val) => {
-                let view = View::new(name.clone());
-                self.views.push(Rc::downgrade(&view.data));
-                let mut ch: Box<GenericSignal + 'a> = match point.ty {
-                    PointType::D1 => {
-                        let ds: Box<DrawStyle<D1>> = Box::new(Lines::new(self.display));
-                        Box::new(Signal::new(name.clone(), ds, view, self.display))
-                    }
-                    PointType::D2 => {
-                        let ds: Box<DrawStyle<D2>> = Box::new(Lines::new(self.display));
-                        Box::new(Signal::new(name.clone(), ds, view, self.display))
-                    }
-                    PointType::D3 => {
-                        let ds: Box<DrawStyle<D3>> = Box::new(Scatter::new(self.display));
-                        Box::new(Signal::new(name.clone(), ds, view, self.display))
-                    }
-                    PointType::BreakPoint => {
-                        let ds: Box<DrawStyle<D1>> = Box::new(Scatter::new(self.display));
-                        Box::new(Signal::new(name.clone(), ds, view, self.display))
-                    }
-                };
-                ch.add_point(point);
-                val.insert(ch);
-                println!("New Signal: {:?}", name)
+        let pos = self.log.end();
+        self.add_point_at(point, pos);
+    }
+
+    fn add_point_at(&mut self, point: MsgPoint, log_pos: usize) {
+        let takes = match self.acquire {
+            Acquire::Run => true,
+            Acquire::Stop => false,
+            Acquire::Single(ref mut single) => single.takes(&point),
+        };
+        if !takes && self.acquire != Acquire::Stop {
+            println!("Single capture done, {} points held", self.held.len() + 1);
+            self.acquire = Acquire::Stop;
+        }
+        if self.paused.is_some() || !takes {
+            self.hold_point(point, log_pos);
+            return;
+        }
+        if point.ty == PointType::BreakPoint {
+            self.hit(point, log_pos);
+            return;
+        }
+        let name = point.name.clone(); // Shared with the parser, cloning it does not copy the name
+        let rslt = match self.signals.get_mut(&*name) {
+            Some(ch) => ch.add_point(point),
+            None => {
+                let mut ch = self.new_signal(String::from(&*name), point.ty);
+                if let Some(h) = self.unplotted.remove(&*name) {
+                    *ch.get_health_mut() = h;
+                }
+                let rslt = ch.add_point(point);
+                self.signals.insert(String::from(&*name), ch);
+                println!("New Signal: {:?}", name);
+                rslt
+            }
+        };
+        if let Err(e) = rslt {
+            // Only the first is printed, the rest are counted in the signal's health
+            if let Some(ch) = self.signals.get_mut(&*name) {
+                let health = ch.get_health_mut();
+                if health.errors == 0 {
+                    eprintln!("{}: {}", name, e);
+                }
+                health.report(e);
             }
+            return;
         }
         self.point_count += 1;
+        if self.memory.over() {
+            self.spill();
+        }
+    }
+
+    // Write out the oldest points of whichever signal holds the most memory until the budget has room again
+    pub fn spill(&mut self) {
+        while !self.memory.settled() {
+            let spilled = match self.signals.values_mut().max_by_key(|s| s.resident()) {
+                Some(s) => s.spill(),
+                None => false,
+            };
+            if !spilled {
+                break;
+            }
+        }
+    }
+
+    fn hit(&mut self, point: MsgPoint, log_pos: usize) {
+        println!("Breakpoint {} at line {}", point.name, point.line_number);
+        self.breaks.push(Break {
+            name: String::from(&*point.name),
+            received: point.timestamp,
+            device: point.device_time,
+            line_number: point.line_number,
+            log_pos,
+        });
+        if self.break_on {
+            self.paused = Some(self.breaks.len() - 1);
+        }
+    }
+
+    // Plots everything that arrived while stopped, up to the next breakpoint
+    pub fn resume(&mut self) {
+        self.paused = None;
+        self.release();
+    }
+
+    // Keeps a point until the display runs again. Block never drops one here, points stop being taken from the queue
+    // instead once the hold is full, see holding_full
+    fn hold_point(&mut self, point: MsgPoint, log_pos: usize) {
+        self.held_offered += 1;
+        let full = self.held.len() >= self.hold;
+        let keep = match self.overload {
+            Overload::Block => true,
+            Overload::DropOldest => {
+                if full {
+                    self.held.pop_front();
+                    self.held_dropped += 1;
+                }
+                true
+            }
+            Overload::DropNewest => !full,
+            Overload::Decimate => {
+                !full && (self.held.len() <= self.hold / 2 || self.held_offered % 2 == 1)
+            }
+        };
+        if keep {
+            self.held.push_back((point, log_pos));
+        } else {
+            self.held_dropped += 1;
+        }
+    }
+
+    // Points should be left queued, so the queues fill and hold up the producers
+    pub fn holding_full(&self) -> bool {
+        self.overload == Overload::Block && self.held.len() >= self.hold
+    }
+
+    fn release(&mut self) {
+        if self.held_dropped > 0 {
+            println!("{} points were dropped while held", self.held_dropped);
+        }
+        self.held_dropped = 0;
+        self.held_offered = 0;
+        let held = std::mem::replace(&mut self.held, VecDeque::new());
+        for (point, log_pos) in held {
+            self.add_point_at(point, log_pos);
+        }
+    }
+
+    // Run plots held points and everything after them, stop holds them from now on and single plots held points until
+    // its capture is done
+    pub fn set_acquire(&mut self, acquire: Acquire) {
+        let release = acquire != Acquire::Stop;
+        self.acquire = acquire;
+        if release {
+            self.release();
+        }
+    }
+
+    pub fn acquire(&self) -> &Acquire {
+        &self.acquire
+    }
+
+    pub fn paused(&self) -> Option<&Break> {
+        self.paused.map(|i| &self.breaks[i])
+    }
+
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    // Points dropped while held since the display last ran
+    pub fn held_dropped(&self) -> usize {
+        self.held_dropped
+    }
+
+    pub fn breaks(&self) -> &[Break] {
+        &self.breaks
+    }
+
+    // Forget the marks of every breakpoint except the one stopped at
+    pub fn clear_breaks(&mut self) {
+        let current = self.paused().cloned();
+        self.breaks.clear();
+        self.paused = current.map(|b| {
+            self.breaks.push(b);
+            0
+        });
+    }
+
+    fn new_signal(&mut self, name: String, ty: PointType) -> Box<GenericSignal + 'a> {
+        let view = View::new(name.clone());
+        self.views.push(Rc::downgrade(&view.data));
+        let mut signal: Box<GenericSignal + 'a> = match ty {
+            PointType::D1 => {
+                let ds: Box<DrawStyle<D1>> = Box::new(Lines::new(self.display));
+                Box::new(Signal::new(name, ds, view, self.display))
+            }
+            PointType::D2 => {
+                let ds: Box<DrawStyle<D2>> = Box::new(Lines::new(self.display));
+                Box::new(Signal::new(name, ds, view, self.display))
+            }
+            PointType::D3 => {
+                let ds: Box<DrawStyle<D3>> = Box::new(Scatter::new(self.display));
+                Box::new(Signal::new(name, ds, view, self.display))
+            }
+            PointType::BreakPoint => {
+                let ds: Box<DrawStyle<D1>> = Box::new(Scatter::new(self.display));
+                Box::new(Signal::new(name, ds, view, self.display))
+            }
+            PointType::List => Box::new(ListSignal::new(name, view, self.display)),
+            PointType::Node => Box::new(GraphSignal::new(name, view, self.display)),
+        };
+        signal.set_history(self.history);
+        signal.set_budget(self.memory.clone());
+        signal
+    }
+
+    // Mark the signals a bad line was meant for, including those not plotted yet
+    pub fn report(&mut self, err: &ParseError) {
+        for name in err.signal_names() {
+            let health = match self.signals.get_mut(name) {
+                Some(s) => s.get_health_mut(),
+                None => self
+                    .unplotted
+                    .entry(String::from(name))
+                    .or_insert_with(Health::new),
+            };
+            health.report(err.to_string());
+        }
+        eprintln!("{}", err); // stdout carries the passed through lines
+    }
+
+    // Names errors were reported for that no signal has been made for
+    pub fn unplotted(&mut self) -> &mut BTreeMap<String, Health> {
+        &mut self.unplotted
     }
 
     pub fn draw_signals(&self, target: &mut glium::Frame, area: Rect) {
@@ -763,3 +1947,156 @@ pub struct PickData {
     pub index: usize,
     pub screen_pos: (f32, f32),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deque(n: usize) -> RangedDeque<D1> {
+        let mut d = RangedDeque::new();
+        for i in 0..n {
+            d.push(Point::new(D1([i as f64, (i % 7) as f64]), i as f64, NAN, i));
+        }
+        d
+    }
+
+    // Range worked out from every point kept
+    fn brute_range(d: &RangedDeque<D1>) -> Range {
+        let mut range = Range::new_cap(2);
+        for pt in d.iter() {
+            for i in 0..2 {
+                range.min[i] = range.min[i].min(pt.axes.0[i]);
+                range.max[i] = range.max[i].max(pt.axes.0[i]);
+            }
+        }
+        range
+    }
+
+    fn wave(i: usize) -> f64 {
+        ((i * 7919) % 113) as f64 - 50.
+    }
+
+    fn check_range(d: &RangedDeque<D1>) {
+        let (got, want) = (d.get_range(), brute_range(d));
+        assert_eq!((got.min, got.max), (want.min, want.max));
+    }
+
+    #[test]
+    fn eviction_by_points_keeps_the_range() {
+        let mut d = RangedDeque::new();
+        for i in 0..500 {
+            d.push(Point::new(D1([i as f64, wave(i)]), i as f64, NAN, i));
+            let popped = d.evict(&History::Points(20));
+            assert_eq!(popped, if i >= 20 { 1 } else { 0 });
+            assert_eq!(d.len(), (i + 1).min(20));
+            assert_eq!(d.get_first().unwrap().line, (i + 1).saturating_sub(20));
+            check_range(&d);
+        }
+        assert_eq!(d.evict(&History::Points(5)), 15);
+        check_range(&d);
+    }
+
+    #[test]
+    fn eviction_by_seconds_keeps_the_range() {
+        let mut d = RangedDeque::new();
+        for i in 0..500 {
+            let received = i as f64 * 1e5; // Ten points a second
+            d.push(Point::new(D1([received, wave(i)]), received, NAN, i));
+            d.evict(&History::Seconds(1.));
+            let first = d.get_first().unwrap().received;
+            assert!(received - first <= 1e6);
+            assert_eq!(d.len(), (i + 1).min(11));
+            check_range(&d);
+        }
+        d.evict(&History::Unbounded);
+        assert_eq!(d.len(), 11);
+    }
+
+    #[test]
+    fn eviction_through_spilled_points_keeps_the_range() {
+        let budget = Budget::new(Some(0));
+        let mut d = RangedDeque::new();
+        d.set_budget(budget.clone());
+        for i in 0..300 {
+            d.push(Point::new(D1([i as f64, wave(i)]), i as f64, NAN, i));
+            if i % 32 == 0 {
+                d.spill();
+            }
+            d.evict(&History::Points(100));
+            check_range(&d);
+        }
+        assert!(d.spilled_len() > 0);
+        assert_eq!(d.get_first().unwrap().line, 200);
+    }
+
+    #[test]
+    fn histories_parse() {
+        assert_eq!("off".parse(), Ok(History::Unbounded));
+        assert_eq!("1000".parse(), Ok(History::Points(1000)));
+        assert_eq!("2.5s".parse(), Ok(History::Seconds(2.5)));
+        assert!("0".parse::<History>().is_err());
+        assert!("-1s".parse::<History>().is_err());
+        assert!("lots".parse::<History>().is_err());
+    }
+
+    #[test]
+    fn spilled_points_read_back_in_order() {
+        let budget = Budget::new(Some(0));
+        let mut d = deque(10);
+        d.set_budget(budget.clone());
+        assert_eq!(d.resident(), 10 * size_of::<Point<D1>>());
+        assert!(d.spill());
+        assert_eq!(d.resident(), 0);
+        assert_eq!(budget.used(), 0);
+        assert!(!d.spill());
+        d.push(Point::new(D1([10., 3.]), 10., NAN, 10));
+        assert_eq!(d.len(), 11);
+        for (i, pt) in d.iter().enumerate() {
+            assert_eq!(pt.axes.0[0], i as f64);
+            assert_eq!(pt.line, i);
+        }
+        assert_eq!(d.position(0, 4.5), 5);
+        assert_eq!(d.get_last().unwrap().line, 10);
+    }
+
+    #[test]
+    fn popping_spilled_points_keeps_the_range() {
+        let budget = Budget::new(Some(0));
+        let mut d = deque(10);
+        d.set_budget(budget.clone());
+        d.spill();
+        for i in 0..10 {
+            assert_eq!(d.get_first().unwrap().line, i);
+            assert!(d.pop());
+        }
+        assert_eq!(d.spilled_len(), 0);
+        d.push(Point::new(D1([10., 3.]), 10., NAN, 10));
+        let range = d.get_range();
+        assert_eq!((range.min[1], range.max[1]), (3., 3.));
+        assert!(d.pop());
+        assert!(!d.pop());
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn runs_of_sorted_times_are_looked_up_on_their_own() {
+        let mut d = RangedDeque::new();
+        for &t in &[0., 1., 2., 3., 1.5, 2.5, 3.5] {
+            d.push(Point::new(D1([t, 0.]), t, NAN, 0));
+        }
+        assert_eq!(d.position_in(0, 1.2, 0, 4), 2);
+        assert_eq!(d.position_in(0, 1.2, 4, 7), 4);
+        assert_eq!(d.position_in(0, 3., 4, 7), 6);
+        assert_eq!(d.position_in(0, 9., 4, 7), 7);
+    }
+
+    #[test]
+    fn dropping_a_deque_gives_back_its_memory() {
+        let budget = Budget::new(None);
+        let mut d = deque(4);
+        d.set_budget(budget.clone());
+        assert_eq!(budget.used(), 4 * size_of::<Point<D1>>());
+        drop(d);
+        assert_eq!(budget.used(), 0);
+    }
+}
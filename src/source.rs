@@ -0,0 +1,381 @@
+// Places lines of input come from. Every source runs on its own thread, reads as much as is available at once and sends
+// the complete lines of it, timestamped together, down a shared channel to the parsing thread. A read is sent in pieces
+// of at most CHUNK_LINES lines so what the queues hold and drop stays close to whole lines. Binary sources are decoded
+// on their own thread and send the samples of a read instead, CHUNK_LINES at a time
+use binary::Decoder;
+use ingest::{duration2us, MsgPoint, ParseError};
+use queue::{Sender, Weight};
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::mem;
+use std::net::{TcpListener, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct Line {
+    pub time: Duration, // Time since epoch that the line was read
+    pub data: Data,
+    pub number: usize, // Position of the (first) line or frame in the stream it came from
+    pub origin: Option<String>, // Which connection sent the line, None for stdin
+}
+
+#[derive(Debug)]
+pub enum Data {
+    Text(String),                               // One or more whole lines
+    Samples(Vec<Result<MsgPoint, ParseError>>), // Already decoded from binary frames, one per frame
+}
+
+impl Weight for Line {
+    fn weight(&self) -> usize {
+        match self.data {
+            Data::Text(ref t) => t.matches('\n').count().max(1),
+            Data::Samples(ref s) => s.len().max(1),
+        }
+    }
+}
+
+pub const CHUNK_LINES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Text,
+    Binary, // Frames described in binary.rs
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Stdin,
+    Tcp(String),  // Address to listen on, every connection is read concurrently
+    Udp(String),  // Address to bind, datagrams may hold several lines
+    Unix(String), // Path of the socket to create
+}
+
+impl Source {
+    pub fn spawn(
+        self,
+        protocol: Protocol,
+        epoch: Instant,
+        tx: Sender<Line>,
+    ) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let rslt = match self {
+                Source::Stdin => {
+                    read_stream(io::stdin(), protocol, None, epoch, &tx);
+                    println!("EOF Reached"); //TODO: Visual signal on key that pipe is closed
+                    Ok(())
+                }
+                Source::Tcp(ref addr) => listen_tcp(addr, protocol, epoch, &tx),
+                Source::Udp(ref addr) => listen_udp(addr, protocol, epoch, &tx),
+                Source::Unix(ref path) => listen_unix(path, protocol, epoch, &tx),
+            };
+            if let Err(e) = rslt {
+                println!("{:?}: {}", self, e);
+            }
+        })
+    }
+
+    // How the user refers to a source, stdin or the address or path it was given
+    pub fn name(&self) -> &str {
+        match *self {
+            Source::Stdin => "stdin",
+            Source::Tcp(ref a) | Source::Udp(ref a) | Source::Unix(ref a) => a,
+        }
+    }
+}
+
+fn send(tx: &Sender<Line>, line: Line) {
+    let number = line.number;
+    if tx.send(line).is_err() {
+        println!("Line {} lost, nothing is parsing input", number);
+    }
+}
+
+fn read_stream<R: Read>(
+    input: R,
+    protocol: Protocol,
+    origin: Option<String>,
+    epoch: Instant,
+    tx: &Sender<Line>,
+) {
+    match protocol {
+        Protocol::Text => read_lines(input, origin, epoch, tx),
+        Protocol::Binary => read_frames(input, origin, epoch, tx),
+    }
+}
+
+const CHUNK: usize = 65536;
+const MAX_LINE: usize = 1 << 20; // Longer lines are dropped rather than held until they end, which may be never
+
+fn read_lines<R: Read>(mut input: R, origin: Option<String>, epoch: Instant, tx: &Sender<Line>) {
+    let mut number = 0usize;
+    let mut buffer = vec![0u8; CHUNK];
+    let mut pending = Vec::with_capacity(CHUNK); // Read but not yet sent, ends in a partial line
+    let mut dropping = false; // Rest of an overlong line is skipped up to its newline
+    loop {
+        match input.read(&mut buffer) {
+            Ok(0) => break, // EOF Reached
+            Ok(n) => {
+                let mut read = &buffer[..n];
+                if dropping {
+                    match read.iter().position(|b| *b == b'\n') {
+                        Some(i) => read = &read[i + 1..],
+                        None => continue,
+                    }
+                    dropping = false;
+                }
+                pending.extend_from_slice(read);
+                if let Some(end) = pending.iter().rposition(|b| *b == b'\n') {
+                    let rest = pending.split_off(end + 1);
+                    let lines = mem::replace(&mut pending, rest);
+                    number = send_text(tx, lines, number, epoch.elapsed(), &origin);
+                }
+                if pending.len() > MAX_LINE {
+                    eprintln!(
+                        "Dropped line {} from {}, longer than {} bytes",
+                        number,
+                        origin.as_deref().unwrap_or("stdin"),
+                        MAX_LINE
+                    );
+                    pending.clear();
+                    number += 1;
+                    dropping = true;
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => {
+                println!("{:?}", error);
+                break;
+            }
+        }
+    }
+    if !pending.is_empty() {
+        send_text(tx, pending, number, epoch.elapsed(), &origin);
+    }
+}
+
+// Returns the number of the line after the last one sent
+fn send_text(
+    tx: &Sender<Line>,
+    bytes: Vec<u8>,
+    number: usize,
+    time: Duration,
+    origin: &Option<String>,
+) -> usize {
+    let text = match String::from_utf8(bytes) {
+        Ok(t) => t,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    };
+    let mut number = number;
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let end = rest
+            .match_indices('\n')
+            .nth(CHUNK_LINES - 1)
+            .map_or(rest.len(), |(i, _)| i + 1);
+        let (piece, after) = rest.split_at(end);
+        let count = piece.lines().count();
+        send(
+            tx,
+            Line {
+                time,
+                data: Data::Text(String::from(piece)),
+                number,
+                origin: origin.clone(),
+            },
+        );
+        number += count;
+        rest = after;
+    }
+    number
+}
+
+fn read_frames<R: Read>(mut input: R, origin: Option<String>, epoch: Instant, tx: &Sender<Line>) {
+    let mut decoder = Decoder::new();
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        match input.read(&mut buffer) {
+            Ok(0) => break, // EOF Reached
+            Ok(n) => {
+                let time = epoch.elapsed();
+                send_samples(tx, &mut decoder, &buffer[..n], time, &origin);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => {
+                println!("{:?}", error);
+                break;
+            }
+        }
+    }
+}
+
+fn send_samples(
+    tx: &Sender<Line>,
+    decoder: &mut Decoder,
+    bytes: &[u8],
+    time: Duration,
+    origin: &Option<String>,
+) {
+    let mut samples = decoder.feed(bytes, duration2us(&time));
+    while !samples.is_empty() {
+        let rest = samples.split_off(samples.len().min(CHUNK_LINES));
+        let number = match samples[0] {
+            Ok(ref p) => p.line_number,
+            Err(_) => decoder.frames,
+        };
+        send(
+            tx,
+            Line {
+                time,
+                data: Data::Samples(samples),
+                number,
+                origin: origin.clone(),
+            },
+        );
+        samples = rest;
+    }
+}
+
+fn listen_tcp(addr: &str, protocol: Protocol, epoch: Instant, tx: &Sender<Line>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    println!(
+        "Listening for tcp connections on {}",
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let origin = stream.peer_addr()?.to_string();
+        println!("New connection: {}", origin);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            read_stream(stream, protocol, Some(origin.clone()), epoch, &tx);
+            println!("Connection closed: {}", origin);
+        });
+    }
+    Ok(())
+}
+
+fn listen_udp(addr: &str, protocol: Protocol, epoch: Instant, tx: &Sender<Line>) -> io::Result<()> {
+    let socket = UdpSocket::bind(addr)?;
+    println!("Listening for udp datagrams on {}", socket.local_addr()?);
+    let mut numbers = HashMap::new(); // Line count of every sender
+    let mut decoders = HashMap::new(); // Frames may be split across datagrams so every sender needs its own decoder
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let (n, from) = socket.recv_from(&mut buffer)?;
+        let time = epoch.elapsed();
+        let origin = from.to_string();
+        if protocol == Protocol::Binary {
+            let decoder = decoders.entry(from).or_insert_with(Decoder::new);
+            send_samples(tx, decoder, &buffer[..n], time, &Some(origin));
+            continue;
+        }
+        let number = numbers.entry(from).or_insert(0usize);
+        let mut text = buffer[..n].to_vec();
+        if text.last() != Some(&b'\n') {
+            text.push(b'\n'); // Datagrams are whole lines even without the newline
+        }
+        *number = send_text(tx, text, *number, time, &Some(origin));
+    }
+}
+
+#[cfg(unix)]
+fn listen_unix(
+    path: &str,
+    protocol: Protocol,
+    epoch: Instant,
+    tx: &Sender<Line>,
+) -> io::Result<()> {
+    let listener = UnixListener::bind(path)?;
+    println!("Listening for connections on {}", path);
+    for (count, stream) in listener.incoming().enumerate() {
+        let stream = stream?;
+        let origin = format!("{}#{}", path, count);
+        println!("New connection: {}", origin);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            read_stream(stream, protocol, Some(origin.clone()), epoch, &tx);
+            println!("Connection closed: {}", origin);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen_unix(
+    _path: &str,
+    _protocol: Protocol,
+    _epoch: Instant,
+    _tx: &Sender<Line>,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "unix sockets are not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binary;
+    use queue::{self, Overload, Stats};
+
+    #[test]
+    fn reads_are_sent_in_pieces() {
+        let (tx, rx) = queue::bounded(10_000, Overload::Block, &Stats::default());
+        let text: String = (0..600).map(|i| format!("{}\n", i)).collect();
+        let input = text + "partial";
+        read_lines(input.as_bytes(), None, Instant::now(), &tx);
+        let mut got = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            let lines = line.weight();
+            match line.data {
+                Data::Text(t) => {
+                    assert!(t.starts_with(&format!("{}\n", line.number)) || t == "partial")
+                }
+                _ => panic!(),
+            }
+            got.push((line.number, lines));
+        }
+        assert_eq!(got, vec![(0, 256), (256, 256), (512, 88), (600, 1)]);
+    }
+
+    #[test]
+    fn overlong_lines_are_dropped() {
+        let (tx, rx) = queue::bounded(10_000, Overload::Block, &Stats::default());
+        let input = format!("a\n{}\nb\n", "x".repeat(MAX_LINE + CHUNK));
+        read_lines(input.as_bytes(), None, Instant::now(), &tx);
+        let mut got = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            match line.data {
+                Data::Text(t) => got.push((line.number, t)),
+                _ => panic!(),
+            }
+        }
+        assert_eq!(
+            got,
+            vec![(0, String::from("a\n")), (2, String::from("b\n"))]
+        );
+    }
+
+    #[test]
+    fn samples_are_sent_in_pieces() {
+        let (tx, rx) = queue::bounded(10_000, Overload::Block, &Stats::default());
+        let mut bytes = Vec::new();
+        for i in 0..300u16 {
+            let mut body = i.to_le_bytes().to_vec();
+            body.push(1);
+            body.extend_from_slice(&1f32.to_le_bytes());
+            bytes.extend(binary::encode(binary::SAMPLE_FRAME, &body));
+        }
+        read_frames(&bytes[..], None, Instant::now(), &tx);
+        let mut got = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            got.push((line.number, line.weight()));
+        }
+        assert_eq!(got, vec![(0, 256), (256, 44)]);
+    }
+}
@@ -0,0 +1,341 @@
+// Older points written out of memory once the points kept by every store together go over a budget. A store holds
+// records of a fixed number of f64 in chunks, each chunk is a file in a temporary directory that is mapped back in, so
+// what is read again is paged in by the system and can be dropped again under pressure. Records are only appended in
+// whole chunks and popped from the front, a chunk file is removed once every record in it was popped
+extern crate memmap2;
+
+use self::memmap2::Mmap;
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static BUDGETS: AtomicUsize = AtomicUsize::new(0); // Made so far, each gets its own directory
+
+// Bytes kept in memory, shared by every store. The directory is made on the first spill and removed with the budget
+pub struct Budget {
+    limit: Cell<Option<usize>>, // Nothing is spilled without one
+    used: Cell<usize>,
+    dir: RefCell<Option<PathBuf>>,
+    id: usize,
+    files: Cell<usize>, // Files made so far, to name the next one
+}
+
+impl Budget {
+    pub fn new(limit: Option<usize>) -> Rc<Budget> {
+        Rc::new(Budget {
+            limit: Cell::new(limit),
+            used: Cell::new(0),
+            dir: RefCell::new(None),
+            id: BUDGETS.fetch_add(1, Ordering::Relaxed),
+            files: Cell::new(0),
+        })
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit.get()
+    }
+
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.set(limit);
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    pub fn charge(&self, bytes: usize) {
+        self.used.set(self.used.get() + bytes);
+    }
+
+    pub fn release(&self, bytes: usize) {
+        self.used.set(self.used.get().saturating_sub(bytes));
+    }
+
+    pub fn over(&self) -> bool {
+        self.limit.get().is_some_and(|l| self.used.get() > l)
+    }
+
+    // Spilling goes on until a quarter of the limit is free, so it happens in large pieces rather than on every point
+    pub fn settled(&self) -> bool {
+        self.limit
+            .get()
+            .is_none_or(|l| self.used.get() <= l - l / 4)
+    }
+
+    fn next_file(&self) -> io::Result<PathBuf> {
+        let mut dir = self.dir.borrow_mut();
+        if dir.is_none() {
+            let d = std::env::temp_dir().join(format!("scope-{}-{}", process::id(), self.id));
+            fs::create_dir_all(&d)?;
+            *dir = Some(d);
+        }
+        let n = self.files.get();
+        self.files.set(n + 1);
+        Ok(dir.as_ref().unwrap().join(format!("{}.bin", n)))
+    }
+}
+
+// Limit such as 512M, 2G or a number of megabytes, or off
+pub fn parse_limit(s: &str) -> Result<Option<usize>, String> {
+    if s == "off" {
+        return Ok(None);
+    }
+    let (number, unit) = match s.chars().last() {
+        Some('K') | Some('k') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') | Some('m') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') | Some('g') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1 << 20),
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n > 0. => Ok(Some((n * unit as f64) as usize)),
+        _ => Err(format!(
+            "Unknown memory limit {:?}, use a size such as 512M or 2G, or off",
+            s
+        )),
+    }
+}
+
+impl Drop for Budget {
+    fn drop(&mut self) {
+        if let Some(ref d) = *self.dir.borrow() {
+            let _ = fs::remove_dir_all(d);
+        }
+    }
+}
+
+struct Chunk {
+    path: PathBuf,
+    map: Mmap,
+    start: usize, // Number of the first record, counting every record pushed
+    len: usize,
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub struct Store {
+    budget: Rc<Budget>,
+    width: usize, // f64 in a record
+    chunks: VecDeque<Chunk>,
+    popped: usize, // Number of the oldest record kept
+    pushed: usize,
+}
+
+impl Store {
+    pub fn new(budget: Rc<Budget>, width: usize) -> Store {
+        Store {
+            budget,
+            width: width.max(1),
+            chunks: VecDeque::new(),
+            popped: 0,
+            pushed: 0,
+        }
+    }
+
+    pub fn budget(&self) -> &Rc<Budget> {
+        &self.budget
+    }
+
+    // Records kept, the oldest is 0
+    pub fn len(&self) -> usize {
+        self.pushed - self.popped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Appends records laid out one after the other as a new chunk
+    pub fn push(&mut self, records: &[f64]) -> io::Result<()> {
+        let len = records.len() / self.width;
+        if len == 0 {
+            return Ok(());
+        }
+        let path = self.budget.next_file()?;
+        let map = write(&path, &records[..len * self.width]).inspect_err(|_| {
+            let _ = fs::remove_file(&path);
+        })?;
+        self.chunks.push_back(Chunk {
+            path,
+            map,
+            start: self.pushed,
+            len,
+        });
+        self.pushed += len;
+        Ok(())
+    }
+
+    // Copies record i into out, which is as wide as a record
+    pub fn get(&self, i: usize, out: &mut [f64]) {
+        let n = self.popped + i;
+        let c = &self.chunks[self.chunks.partition_point(|c| c.start + c.len <= n)];
+        let at = (n - c.start) * self.width * 8;
+        let bytes = &c.map[at..at + self.width * 8];
+        for (o, b) in out.iter_mut().zip(bytes.chunks(8)) {
+            let mut le = [0; 8];
+            le.copy_from_slice(b);
+            *o = f64::from_le_bytes(le);
+        }
+    }
+
+    pub fn pop_front(&mut self) {
+        if self.popped == self.pushed {
+            return;
+        }
+        self.popped += 1;
+        if self
+            .chunks
+            .front()
+            .is_some_and(|c| c.start + c.len == self.popped)
+        {
+            self.chunks.pop_front();
+        }
+    }
+}
+
+fn write(path: &Path, records: &[f64]) -> io::Result<Mmap> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for r in records {
+        out.write_all(&r.to_le_bytes())?;
+    }
+    out.flush()?;
+    drop(out);
+    let file = File::open(path)?;
+    // Only scope writes the file and never after it is mapped
+    unsafe { Mmap::map(&file) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(from: usize, to: usize) -> Vec<f64> {
+        (from..to)
+            .flat_map(|i| vec![i as f64, -(i as f64)])
+            .collect()
+    }
+
+    fn record(store: &Store, i: usize) -> [f64; 2] {
+        let mut out = [0.; 2];
+        store.get(i, &mut out);
+        out
+    }
+
+    #[test]
+    fn records_are_read_back_across_chunks() {
+        let mut store = Store::new(Budget::new(Some(0)), 2);
+        store.push(&records(0, 3)).unwrap();
+        store.push(&records(3, 5)).unwrap();
+        store.push(&records(5, 9)).unwrap();
+        assert_eq!(store.len(), 9);
+        for i in 0..9 {
+            assert_eq!(record(&store, i), [i as f64, -(i as f64)]);
+        }
+    }
+
+    #[test]
+    fn popping_counts_from_the_oldest_kept() {
+        let mut store = Store::new(Budget::new(Some(0)), 2);
+        store.push(&records(0, 3)).unwrap();
+        store.push(&records(3, 5)).unwrap();
+        for _ in 0..4 {
+            store.pop_front();
+        }
+        assert_eq!(store.len(), 1);
+        assert_eq!(record(&store, 0), [4., -4.]);
+        store.push(&records(5, 7)).unwrap();
+        assert_eq!(record(&store, 2), [6., -6.]);
+        for _ in 0..5 {
+            store.pop_front();
+        }
+        assert_eq!(store.len(), 0);
+    }
+
+    #[test]
+    fn partial_records_are_left_out() {
+        let mut store = Store::new(Budget::new(Some(0)), 2);
+        store.push(&[1., 2., 3.]).unwrap();
+        store.push(&[4.]).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.chunks.len(), 1);
+    }
+
+    #[test]
+    fn chunk_files_go_once_popped() {
+        let mut store = Store::new(Budget::new(Some(0)), 2);
+        store.push(&records(0, 2)).unwrap();
+        store.push(&records(2, 4)).unwrap();
+        let first = store.chunks[0].path.clone();
+        let second = store.chunks[1].path.clone();
+        assert!(first.exists() && second.exists());
+        store.pop_front();
+        assert!(first.exists());
+        store.pop_front();
+        assert!(!first.exists());
+        assert!(second.exists());
+        drop(store);
+        assert!(!second.exists());
+    }
+
+    #[test]
+    fn directory_goes_with_the_budget() {
+        let budget = Budget::new(Some(0));
+        let mut store = Store::new(budget.clone(), 1);
+        store.push(&[1.]).unwrap();
+        let dir = budget.dir.borrow().clone().unwrap();
+        assert!(dir.exists());
+        drop(store);
+        assert!(dir.exists());
+        drop(budget);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn budgets_do_not_share_a_directory() {
+        let a = Budget::new(Some(0));
+        let b = Budget::new(Some(0));
+        let pa = a.next_file().unwrap();
+        let pb = b.next_file().unwrap();
+        assert_ne!(pa.parent(), pb.parent());
+    }
+
+    #[test]
+    fn budget_is_over_past_the_limit_and_settled_a_quarter_below() {
+        let budget = Budget::new(Some(100));
+        budget.charge(100);
+        assert!(!budget.over());
+        assert!(!budget.settled());
+        budget.charge(1);
+        assert!(budget.over());
+        budget.release(26);
+        assert!(budget.settled());
+        budget.release(1000);
+        assert_eq!(budget.used(), 0);
+        budget.charge(1 << 40);
+        budget.set_limit(None);
+        assert!(!budget.over());
+        assert!(budget.settled());
+    }
+
+    #[test]
+    fn limits_parse_with_units() {
+        assert_eq!(parse_limit("off"), Ok(None));
+        assert_eq!(parse_limit("512"), Ok(Some(512 << 20)));
+        assert_eq!(parse_limit("512M"), Ok(Some(512 << 20)));
+        assert_eq!(parse_limit("64k"), Ok(Some(64 << 10)));
+        assert_eq!(parse_limit("1.5G"), Ok(Some(3 << 29)));
+        assert!(parse_limit("0").is_err());
+        assert!(parse_limit("-1G").is_err());
+        assert!(parse_limit("lots").is_err());
+        assert!(parse_limit("").is_err());
+    }
+}
@@ -15,7 +15,8 @@ use glimput::Editor;
 use glium::glutin::event::{self, KeyboardInput, VirtualKeyCode as VKC};
 
 use drawstyles::Transform;
-use signal::{SignalHealth, SignalManager};
+use scope::session::Control;
+use signal::{Acquire, SignalHealth, SignalManager, Single};
 
 type Color = (f32, f32, f32, f32);
 const DARK_GREY: Color = (0.01, 0.01, 0.01, 1.0);
@@ -53,6 +54,7 @@ pub struct UI<'a> {
     working_area: (f64, f64, f64, f64),
     lmb_pressed: bool,
     hidpi_factor: f64,
+    cursor_times: Vec<f64>, // Receive times of the points under the cursor
 }
 
 impl<'a> UI<'a> {
@@ -90,6 +92,7 @@ impl<'a> UI<'a> {
             working_area: (0., 0., 0., 0.),
             lmb_pressed: false,
             hidpi_factor: 1.0,
+            cursor_times: Vec::new(),
         }
     }
     pub fn draw(
@@ -109,6 +112,47 @@ impl<'a> UI<'a> {
             (1.0, 1.0, 1.0, 1.0),
             &frametime.floor().to_string(),
         );
+        let mut y = 0.93;
+        for (name, pending, dropped) in self.signal_manager.queues.snapshot() {
+            self.draw_text(
+                target,
+                -0.98,
+                y,
+                0.03,
+                if dropped > 0 {
+                    (1., 0.3, 0.3, 1.)
+                } else {
+                    (0.6, 0.6, 0.6, 1.)
+                },
+                &format!("{}: {} lines queued {} dropped", name, pending, dropped),
+            );
+            y -= 0.03;
+        }
+
+        let acquire = match *self.signal_manager.acquire() {
+            Acquire::Run => None,
+            Acquire::Stop => Some(format!(
+                "STOP, {} held {} dropped",
+                self.signal_manager.held(),
+                self.signal_manager.held_dropped()
+            )),
+            Acquire::Single(ref s) if s.triggered() => Some(String::from("SINGLE, capturing")),
+            Acquire::Single(_) => Some(String::from("SINGLE, waiting for trigger")),
+        };
+        if let Some(text) = acquire {
+            self.draw_text(target, -0.9, 0.97, 0.04, (1., 0.8, 0.2, 1.), &text);
+        }
+        let replay = self.signal_manager.player.as_ref().map(|p| {
+            format!(
+                "REPLAY {:.1}s / {:.1}s{}",
+                p.position() / 1e6,
+                p.length / 1e6,
+                if p.paused() { ", paused" } else { "" }
+            )
+        });
+        if let Some(text) = replay {
+            self.draw_text(target, -0.9, 0.93, 0.04, (0.4, 0.8, 1., 1.), &text);
+        }
 
         // self.debug_perf(frametime);
 
@@ -119,6 +163,7 @@ impl<'a> UI<'a> {
 
         let area = (view_start_x, view_start_y, view_end_x, view_end_y);
         self.working_area = area;
+        self.signal_manager.area = area;
 
         let mut sel = self.signal_manager.get_selection();
         if sel.is_none() {
@@ -128,8 +173,12 @@ impl<'a> UI<'a> {
 
         self.signal_manager.draw_signals(target, area);
 
+        self.draw_breaks(target, area);
+
         self.draw_cursors(target, area);
 
+        self.draw_log(target, area);
+
         self.draw_cmdline(target, area);
 
         self.draw_ledgend(target, view_end_x);
@@ -151,17 +200,31 @@ impl<'a> UI<'a> {
         // let itheta = PI+ 0.75*PI;//av.1.atan2(av.0);
         let itheta = PI / 2.0;
         let mut theta: f64 = itheta;
+        let mut times = Vec::new();
+        let mut under = Vec::new();
         if self.cursor.signal.is_some() {
             self.draw_cursor(target, &self.cursor);
             let pad = 0.01;
             let mut axis_width = 0.125f64;
-            for (_name, sig) in self.signal_manager.iter() {
+            for (name, sig) in self.signal_manager.iter() {
                 if let Some(pick) =
                     sig.pick((self.cursor.pos.0 as f32, self.cursor.pos.1 as f32), area)
                 {
                     let c = sig.get_color();
                     let color = (c.0, c.1, c.2, 1.0);
-                    let (xtext, ytext, ztext) = sig.get_point_strings(pick.index);
+                    let (xtext, ytext, mut ztext) = sig.get_point_strings(pick.index);
+                    if let Some((line, t)) = sig.source(pick.index) {
+                        times.push(t);
+                        under.push((name.clone(), pick.index));
+                        if !ztext.is_empty() {
+                            ztext.push_str(", ");
+                        }
+                        ztext.push_str(&format!("line {}", line));
+                        if let Some(l) = self.signal_manager.sources.get(line, t) {
+                            ztext.push_str(": ");
+                            ztext.extend(l.text.chars().take(60));
+                        }
+                    }
                     let dims = self.get_text_dims(scale, &ztext);
                     let sign = if theta.cos() > 0. { -1. } else { 1. };
                     // let x = pick.screen_pos.0 as f64 + theta.cos()*self.hover_rad;
@@ -235,9 +298,124 @@ impl<'a> UI<'a> {
             self.hover_rad = self.hover_rad.min(0.6);
             self.hover_rad = self.hover_rad.max(0.0001);
         }
+        self.cursor_times = times;
+        self.signal_manager.under_cursor = under;
         // Draw second cursor if exists and draw rulers
     }
 
+    // Marks every breakpoint on the signals plotted against time, and while stopped shows the log around the one
+    // stopped at
+    fn draw_breaks(&self, target: &mut Frame, area: (f64, f64, f64, f64)) {
+        let man = &self.signal_manager;
+        let height = ((area.3 - area.1) * self.window_size.1 as f64 / 2.) as u32;
+        let current = man.paused().map(|b| b.log_pos);
+        for (_name, sig) in man.iter() {
+            for b in man.breaks() {
+                if let Some(x) = sig.time_x(b.received, b.device, area) {
+                    if x < area.0 || x > area.2 {
+                        continue;
+                    }
+                    let color = if current == Some(b.log_pos) {
+                        (1., 0.3, 0.3, 1.)
+                    } else {
+                        (0.5, 0.5, 0.5, 1.)
+                    };
+                    self.draw_rect_px(target, color, (x, area.1), (1, height));
+                }
+            }
+        }
+
+        let b = match man.paused() {
+            Some(b) => b,
+            None => return,
+        };
+        let scale = 0.035;
+        let th = (self.text_height * scale) as f64;
+        let context = 6; // Lines shown either side of the breakpoint
+        let mut lines = vec![(
+            (1., 0.3, 0.3, 1.),
+            format!(
+                "Stopped at breakpoint {} on line {}, {} points held {} dropped. F5 or continue to resume",
+                b.name,
+                b.line_number,
+                man.held(),
+                man.held_dropped()
+            ),
+        )];
+        let grey = (0.7, 0.7, 0.7, 1.);
+        for (_, l) in man.log.range(b.log_pos.saturating_sub(context), b.log_pos) {
+            lines.push((grey, format!("{}: {}", l.number, l.text)));
+        }
+        lines.push(((1., 0.3, 0.3, 1.), String::from("---- breakpoint ----")));
+        for (_, l) in man.log.range(b.log_pos, b.log_pos + context) {
+            lines.push((grey, format!("{}: {}", l.number, l.text)));
+        }
+        let width = area.2 - area.0 - 0.04;
+        let top = area.3 - 0.02;
+        self.draw_rect(
+            target,
+            DARK_GREY,
+            (area.0 + 0.02, top - th * lines.len() as f64 - 0.01),
+            (width, th * lines.len() as f64 + 0.02),
+        );
+        let mut y = top - th / 2.;
+        for (color, text) in lines {
+            self.draw_text(target, area.0 + 0.03, y, scale, color, &text);
+            y -= th;
+        }
+    }
+
+    // Passed through lines with the newest at the bottom, lines received along with the points under the cursor are
+    // highlighted
+    fn draw_log(&self, target: &mut Frame, area: (f64, f64, f64, f64)) {
+        let width = self.get_log_width();
+        if width == 0. {
+            return;
+        }
+        let log = &self.signal_manager.log;
+        let left = area.2 + 0.02;
+        let width = width - 0.03;
+        self.draw_rect(target, DARK_GREY, (left, area.1), (width, area.3 - area.1));
+
+        let scale = 0.03;
+        let th = (self.text_height * scale) as f64;
+        let chars = ((width - 0.02) / self.get_text_dims(scale, "0").0.max(1e-6)) as usize;
+        let mut header = String::from("log");
+        if !log.filter.is_empty() {
+            header.push_str(&format!(" containing {:?}", log.filter));
+        }
+        if log.scroll > 0 {
+            header.push_str(&format!(", {} newer lines below", log.scroll));
+        }
+        self.draw_text(
+            target,
+            left + 0.01,
+            area.3 - th / 2.,
+            scale,
+            (1., 1., 1., 1.),
+            &header,
+        );
+
+        let rows = ((area.3 - area.1) / th) as usize;
+        let mut y = area.1 + th / 2.;
+        for l in log.shown().take(rows.saturating_sub(1)) {
+            let color = if self.cursor_times.iter().any(|t| (l.time - t).abs() < 1.) {
+                (1., 0.85, 0.3, 1.)
+            } else {
+                (0.7, 0.7, 0.7, 1.)
+            };
+            let mut text = format!("{:9.3} {:6} ", l.time / 1e6, l.number);
+            if let Some(ref o) = l.origin {
+                text.push_str(o);
+                text.push_str(": ");
+            }
+            text.push_str(&l.text);
+            let text: String = text.chars().take(chars).collect();
+            self.draw_text(target, left + 0.01, y, scale, color, &text);
+            y += th;
+        }
+    }
+
     fn draw_cursor(&self, target: &mut Frame, cursor: &DataCursor) {
         self.draw_rect_px(
             target,
@@ -278,6 +456,36 @@ impl<'a> UI<'a> {
                         }
                         //TODO: there has to be a better way to do this
                     }
+                    VKC::F5 => self.signal_manager.resume(),
+                    VKC::F6 => {
+                        let next = match *self.signal_manager.acquire() {
+                            Acquire::Run => Acquire::Stop,
+                            _ => Acquire::Run,
+                        };
+                        self.signal_manager.set_acquire(next);
+                    }
+                    VKC::F9 => {
+                        if let Some(sig) = self.signal_manager.get_selected() {
+                            let view = sig.get_view();
+                            let follow = !view.following();
+                            view.set_follow(follow);
+                        }
+                    }
+                    VKC::F8 => {
+                        if let Some(ref p) = self.signal_manager.player {
+                            p.send(if p.paused() {
+                                Control::Play
+                            } else {
+                                Control::Pause
+                            });
+                        }
+                    }
+                    VKC::F7 => self
+                        .signal_manager
+                        .set_acquire(Acquire::Single(Single::new(None, 1e6))),
+                    VKC::PageUp => self.signal_manager.log.scroll_by(10),
+                    VKC::PageDown => self.signal_manager.log.scroll_by(-10),
+                    VKC::End => self.signal_manager.log.scroll = 0,
                     VKC::Return => {
                         let rslt = command_parse::parse(
                             self.editor.get_buffer(),
@@ -305,7 +513,13 @@ impl<'a> UI<'a> {
                 modifiers: _,
                 ..
             } => {
-                if let Some(sig) = self.signal_manager.get_selected() {
+                let over_log = self.cursor.pos.0 > self.working_area.2
+                    && self.cursor.pos.0 < self.working_area.2 + self.get_log_width();
+                if over_log {
+                    if let glium::glutin::event::MouseScrollDelta::LineDelta(_, y) = delta {
+                        self.signal_manager.log.scroll_by((*y * 3.) as isize);
+                    }
+                } else if let Some(sig) = self.signal_manager.get_selected() {
                     if let glium::glutin::event::MouseScrollDelta::LineDelta(_, y) = delta {
                         sig.zoom_by(
                             *y as f64,
@@ -330,12 +544,13 @@ impl<'a> UI<'a> {
                 }
             }
             event::WindowEvent::CursorMoved { position, .. } => {
+                // Tracked without a selection too so the log pane can be scrolled
+                self.last_mouse_pos = self.cursor.pos;
+                self.cursor.pos = (
+                    (2. * (position.x * self.hidpi_factor / (self.window_size.0 as f64)) - 1.),
+                    (1. - 2. * (position.y * self.hidpi_factor / (self.window_size.1 as f64))),
+                );
                 if let Some(sig) = self.signal_manager.get_selected() {
-                    self.last_mouse_pos = self.cursor.pos;
-                    self.cursor.pos = (
-                        (2. * (position.x * self.hidpi_factor / (self.window_size.0 as f64)) - 1.),
-                        (1. - 2. * (position.y * self.hidpi_factor / (self.window_size.1 as f64))),
-                    );
                     self.cursor.signal = Some(sig.get_name().clone());
                     if self.lmb_pressed {
                         let delta = (
@@ -442,7 +657,7 @@ impl<'a> UI<'a> {
             }
             self.draw_rect(
                 target,
-                match sig.get_health() {
+                match sig.get_health().state {
                     SignalHealth::Good => (62.0 / 256.0, 107.0 / 256.0, 12.0 / 256.0, 1.),
                     SignalHealth::InvalidFormat => (1., 0., 0., 1.),
                 },
@@ -513,18 +728,18 @@ impl<'a> UI<'a> {
         text: &str,
     ) -> (f64, f64) {
         let trans = Transform {
-            dx: x as f32,
-            dy: y as f32 - self.text_height * scale / 2.9,
-            sx: scale * self.resquare(),
-            sy: scale,
+            dx: x,
+            dy: y - (self.text_height * scale / 2.9) as f64,
+            sx: (scale * self.resquare()) as f64,
+            sy: scale as f64,
             sz: 1.,
         };
         let mut tf = self.text_format.borrow_mut();
         tf.set_text(text);
         glium_text::draw(&tf, &self.text_system, target, &trans.into(), color).unwrap();
         (
-            (tf.get_width() * trans.sx) as f64,
-            (tf.get_height() * trans.sy * 1.3) as f64,
+            tf.get_width() as f64 * trans.sx,
+            tf.get_height() as f64 * trans.sy * 1.3,
         )
     }
 
@@ -537,8 +752,14 @@ impl<'a> UI<'a> {
         )
     }
 
+    // Pane only takes room once something has been passed through
     fn get_log_width(&self) -> f64 {
-        0.0
+        let log = &self.signal_manager.log;
+        if log.visible && log.end() > 0 {
+            0.5
+        } else {
+            0.0
+        }
     }
 
     fn get_ledgend_width(&self) -> f64 {